@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, bail, Context};
+use rdkafka::producer::FutureProducer;
+
+use crate::{
+    cli::{
+        print_failures, print_records, produce_all, read_ids, Action, ActionResult, ActionStatus,
+        BulkArgs, BulkSelection, MessageSelector, OutputFormat, ProduceLimits, Progress,
+    },
+    envelope,
+    kafka::{producer_config, ParseFailurePolicy, ScanMode},
+    settings::Settings,
+};
+
+/// Archiving is not rate limited; at most 100 records wait for delivery.
+const ARCHIVE_LIMITS: ProduceLimits = ProduceLimits { rate: None, max_in_flight: 100 };
+
+pub async fn archive_messages(
+    settings: &Settings,
+    topic: &str,
+    selector: &MessageSelector,
+    bulk: &BulkArgs,
+    output: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    let ids = bulk.ids.as_deref().map(read_ids).transpose()?;
+    match &ids {
+        None if selector.is_empty() => {
+            bail!("Give --ids or filter criteria (--filter, --where, --key, --header, --since, --until)")
+        }
+        Some(ids) if ids.is_empty() => bail!("The ID list is empty"),
+        _ => {}
+    }
+
+    let archive_topic = settings.archive_topic.as_str();
+    let mut scanner = selector
+        .scanner(settings, topic, ScanMode::Group)?
+        .on_parse_failure(ParseFailurePolicy::Keep);
+    let selection = BulkSelection::scan(&mut scanner, selector, ids.as_deref()).await?;
+    let mapping = scanner.mapping().clone();
+    let total = selection.records.len();
+
+    if output.is_table() {
+        println!("{} message(s) to archive from {} to {}", total, topic, archive_topic);
+        selection.print_plan();
+    }
+    if bulk.dry_run || total == 0 {
+        if output.is_table() {
+            println!("{}: {}", topic, scanner.summary());
+        } else {
+            let planned: Vec<ActionResult> = selection
+                .records
+                .iter()
+                .map(|record| ActionResult::new(Action::Archive, ActionStatus::Planned, record, Some(archive_topic)))
+                .collect();
+            print_records(output, ActionResult::FIELDS, &planned)?;
+        }
+        return Ok(());
+    }
+
+    let producer: FutureProducer = producer_config(settings)
+        .create()
+        .context("Producer creation error")?;
+    let archived_at = chrono::Utc::now().to_rfc3339();
+
+    let mut results: Vec<ActionResult> = Vec::with_capacity(total);
+    let mut handled: HashSet<(i32, i64)> = HashSet::new();
+    let mut progress = Progress::new("Archived", total);
+    let jobs = selection.records.iter().map(|record| {
+        let archived = envelope::archive(record, &archived_at, &mapping)?;
+        Ok((archive_topic.to_string(), archived))
+    });
+    produce_all(&producer, jobs, ARCHIVE_LIMITS, |index, delivered| {
+        let record = &selection.records[index];
+        progress.record(delivered.is_ok());
+        results.push(match delivered {
+            Ok(()) => {
+                handled.insert((record.partition, record.offset));
+                ActionResult::new(Action::Archive, ActionStatus::Archived, record, Some(archive_topic))
+            }
+            Err(e) => ActionResult::failed(Action::Archive, record, Some(archive_topic), &e),
+        });
+    })
+    .await;
+    progress.finish();
+
+    selection.commit(&scanner, &handled)?;
+
+    if output.is_table() {
+        println!("Archived {} of {} message(s) to {}", handled.len(), total, archive_topic);
+        print_failures(&results);
+    } else {
+        print_records(output, ActionResult::FIELDS, &results)?;
+    }
+
+    let failed = total - handled.len();
+    if failed > 0 {
+        return Err(anyhow!("{} message(s) could not be archived", failed));
+    }
+    Ok(())
+}
@@ -0,0 +1,435 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fs,
+    future::Future,
+    io::{self, IsTerminal, Read, Write},
+    path::Path,
+    pin::Pin,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use rdkafka::producer::FutureProducer;
+use tabled::{Table, Tabled};
+use tokio::time::{interval, Interval, MissedTickBehavior};
+
+use crate::{
+    cli::{ActionResult, MessageSelector, ProduceLimits},
+    envelope::OutgoingRecord,
+    kafka::{delivered, queue_record, DlqRecord, DlqScanner},
+};
+
+/// How many IDs from the list to name when some are not found.
+const MISSING_IDS_SHOWN: usize = 10;
+
+/// Message IDs listed in `path`, or on stdin for `-`: one per line, blank
+/// lines and `#` comments ignored.
+pub fn read_ids(path: &Path) -> Result<Vec<String>, anyhow::Error> {
+    let text = if path == Path::new("-") {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .context("Failed to read IDs from stdin")?;
+        text
+    } else {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?
+    };
+
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// The records a bulk command works on, found in a single scan of the DLQ.
+pub struct BulkSelection {
+    /// Selected records in scan order
+    pub records: Vec<DlqRecord>,
+    /// IDs from the list that no selected message has
+    pub missing_ids: Vec<String>,
+    /// Per partition, the first record that was read but not selected,
+    /// including records outside the time range
+    unselected: HashMap<i32, i64>,
+    /// The group's committed offset per partition as of scan start
+    committed: HashMap<i32, i64>,
+}
+
+impl BulkSelection {
+    /// Scan the rest of the topic for records that match `selector` and, if
+    /// `ids` is given, whose id or correlation id is listed. The scanner
+    /// should keep undecodable records so that they count as unselected.
+    pub async fn scan(
+        scanner: &mut DlqScanner,
+        selector: &MessageSelector,
+        ids: Option<&[String]>,
+    ) -> Result<Self, anyhow::Error> {
+        let mapping = scanner.mapping().clone();
+        let listed: Option<HashSet<&str>> = ids.map(|ids| ids.iter().map(String::as_str).collect());
+        let mut found: HashSet<String> = HashSet::new();
+        let mut records = vec![];
+        let mut unselected: HashMap<i32, i64> = HashMap::new();
+
+        while let Some(record) = scanner.next().await? {
+            let is_listed = match &listed {
+                None => true,
+                Some(listed) => {
+                    record.parse_error.is_none()
+                        && (listed.contains(record.message.id.as_str())
+                            || listed.contains(record.message.correlation_id.as_str()))
+                }
+            };
+            if is_listed && selector.matches(&record, &mapping) {
+                if listed.is_some() {
+                    found.insert(record.message.id.clone());
+                    found.insert(record.message.correlation_id.clone());
+                }
+                records.push(record);
+            } else {
+                unselected
+                    .entry(record.partition)
+                    .and_modify(|offset| *offset = (*offset).min(record.offset))
+                    .or_insert(record.offset);
+            }
+        }
+        for (partition, offset) in scanner.skipped() {
+            unselected
+                .entry(*partition)
+                .and_modify(|first| *first = (*first).min(*offset))
+                .or_insert(*offset);
+        }
+
+        let mut missing_ids: Vec<String> = vec![];
+        for id in ids.unwrap_or_default() {
+            if !found.contains(id) && !missing_ids.contains(id) {
+                missing_ids.push(id.clone());
+            }
+        }
+        let committed = scanner.committed().clone();
+        Ok(Self { records, missing_ids, unselected, committed })
+    }
+
+    /// Message counts by reason and original topic, most common first.
+    pub fn print_plan(&self) {
+        let mut groups: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+        for record in self.records.iter().filter(|record| record.parse_error.is_none()) {
+            *groups
+                .entry((&record.message.reason, &record.message.original_topic))
+                .or_default() += 1;
+        }
+        let mut rows: Vec<PlanRow> = groups
+            .into_iter()
+            .map(|((reason, original_topic), messages)| PlanRow {
+                reason: reason.to_string(),
+                original_topic: original_topic.to_string(),
+                messages,
+            })
+            .collect();
+        rows.sort_by_key(|row| std::cmp::Reverse(row.messages));
+        if !rows.is_empty() {
+            println!("{}", Table::new(rows));
+        }
+
+        let unparseable = self.records.iter().filter(|record| record.parse_error.is_some()).count();
+        if unparseable > 0 {
+            println!("Unparseable records: {}", unparseable);
+        }
+        if !self.missing_ids.is_empty() {
+            let shown = &self.missing_ids[..self.missing_ids.len().min(MISSING_IDS_SHOWN)];
+            let more = self.missing_ids.len() - shown.len();
+            println!(
+                "{} listed ID(s) not found: {}{}",
+                self.missing_ids.len(),
+                shown.join(", "),
+                if more > 0 { format!(" and {} more", more) } else { String::new() }
+            );
+        }
+    }
+
+    /// The one selected record, for commands that change a single record, so
+    /// that an ambiguous selection never touches the wrong record.
+    pub fn single(&self, scanner: &DlqScanner) -> Result<&DlqRecord, anyhow::Error> {
+        match self.records.as_slice() {
+            [] => Err(anyhow!("Message not found ({})", scanner.summary())),
+            [record] => Ok(record),
+            records => {
+                let positions: Vec<String> = records
+                    .iter()
+                    .map(|r| format!("{}:{}:{}", r.topic, r.partition, r.offset))
+                    .collect();
+                Err(anyhow!(
+                    "{} messages match ({}); narrow the selection or pass a TOPIC:PARTITION:OFFSET locator",
+                    records.len(),
+                    positions.join(", ")
+                ))
+            }
+        }
+    }
+
+    /// Why handling `record` alone cannot be committed, if it cannot: the
+    /// group already handled it, or an earlier record in its partition is
+    /// still unhandled.
+    pub fn uncommittable(&self, record: &DlqRecord) -> Option<String> {
+        if let Some(committed) = self.committed.get(&record.partition).filter(|c| record.offset < **c) {
+            return Some(format!("the group has already handled it (committed offset {})", committed));
+        }
+        let earlier = self
+            .records
+            .iter()
+            .filter(|other| other.partition == record.partition)
+            .map(|other| other.offset)
+            .chain(self.unselected.get(&record.partition).copied())
+            .filter(|offset| *offset < record.offset)
+            .min()?;
+        Some(format!("offset {} before it has not been handled yet", earlier))
+    }
+
+    /// Commit the consumer group past the handled records of each partition.
+    /// A partition is only committed up to its first record that was not
+    /// selected or not handled, so that no such record is marked handled,
+    /// and never moved back.
+    pub fn commit(&self, scanner: &DlqScanner, handled: &HashSet<(i32, i64)>) -> Result<(), anyhow::Error> {
+        let selected: Vec<(i32, i64)> = self
+            .records
+            .iter()
+            .map(|record| (record.partition, record.offset))
+            .collect();
+        for index in commit_points(&selected, &self.unselected, &self.committed, handled) {
+            scanner.commit(&self.records[index])?;
+        }
+        Ok(())
+    }
+}
+
+/// Indexes into `selected` (partition and offset, in scan order) of the
+/// record to commit past in each partition: the last one before the first
+/// record that is unselected or not handled. Nothing is committed in a
+/// partition whose first selected record is before the committed offset.
+fn commit_points(
+    selected: &[(i32, i64)],
+    unselected: &HashMap<i32, i64>,
+    committed: &HashMap<i32, i64>,
+    handled: &HashSet<(i32, i64)>,
+) -> Vec<usize> {
+    // Per partition: the last record to commit past, and whether a gap was reached
+    let mut partitions: BTreeMap<i32, (Option<usize>, bool)> = BTreeMap::new();
+    for (index, (partition, offset)) in selected.iter().enumerate() {
+        let (last, stopped) = partitions.entry(*partition).or_default();
+        if *stopped {
+            continue;
+        }
+        let after_gap = unselected.get(partition).is_some_and(|first| first < offset);
+        let already_handled = committed.get(partition).is_some_and(|committed| offset < committed);
+        if after_gap || already_handled || !handled.contains(&(*partition, *offset)) {
+            *stopped = true;
+        } else {
+            *last = Some(index);
+        }
+    }
+    partitions.values().filter_map(|(last, _)| *last).collect()
+}
+
+#[derive(Tabled)]
+struct PlanRow {
+    reason: String,
+    original_topic: String,
+    messages: usize,
+}
+
+type Delivery = Pin<Box<dyn Future<Output = Result<(), anyhow::Error>>>>;
+
+/// Produce each record to its topic, in order and within `limits`, and call
+/// `done` with the index of each job and whether its record was delivered.
+/// Jobs that failed to prepare are passed straight to `done`.
+pub async fn produce_all<I>(
+    producer: &FutureProducer,
+    jobs: I,
+    limits: ProduceLimits,
+    mut done: impl FnMut(usize, Result<(), anyhow::Error>),
+) where
+    I: IntoIterator<Item = Result<(String, OutgoingRecord), anyhow::Error>>,
+{
+    let mut jobs = jobs.into_iter().enumerate().peekable();
+    let mut ticker = limits.rate.map(|rate| {
+        let mut ticker = interval(Duration::from_secs_f64(1.0 / rate));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker
+    });
+    let mut in_flight: VecDeque<(usize, Delivery)> = VecDeque::new();
+
+    loop {
+        let can_send = in_flight.len() < limits.max_in_flight as usize && jobs.peek().is_some();
+        if !can_send && in_flight.is_empty() {
+            break;
+        }
+        tokio::select! {
+            // Report deliveries as soon as they arrive
+            biased;
+            result = oldest(&mut in_flight) => {
+                let (index, _) = in_flight.pop_front().expect("a delivery was awaited");
+                done(index, result);
+            }
+            _ = next_slot(&mut ticker), if can_send => {
+                let (index, job) = jobs.next().expect("a job is waiting");
+                let queued = job.and_then(|(topic, record)| {
+                    let delivery = queue_record(producer, &topic, &record)?;
+                    Ok(Box::pin(async move { delivered(delivery, &topic).await }) as Delivery)
+                });
+                match queued {
+                    Ok(delivery) => in_flight.push_back((index, delivery)),
+                    Err(e) => done(index, Err(e)),
+                }
+            }
+        }
+    }
+}
+
+/// The result of the oldest delivery; never ready if nothing is in flight.
+async fn oldest(in_flight: &mut VecDeque<(usize, Delivery)>) -> Result<(), anyhow::Error> {
+    match in_flight.front_mut() {
+        Some((_, delivery)) => delivery.await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn next_slot(ticker: &mut Option<Interval>) {
+    if let Some(ticker) = ticker {
+        ticker.tick().await;
+    }
+}
+
+/// A count of processed records on stderr, redrawn in place when stderr is
+/// a terminal and not shown otherwise.
+pub struct Progress {
+    verb: &'static str,
+    total: usize,
+    done: usize,
+    failed: usize,
+    live: bool,
+}
+
+impl Progress {
+    pub fn new(verb: &'static str, total: usize) -> Self {
+        Self { verb, total, done: 0, failed: 0, live: io::stderr().is_terminal() }
+    }
+
+    pub fn record(&mut self, succeeded: bool) {
+        self.done += 1;
+        if !succeeded {
+            self.failed += 1;
+        }
+        if self.live {
+            eprint!("\r{} {}/{}", self.verb, self.done - self.failed, self.total);
+            if self.failed > 0 {
+                eprint!(", {} failed", self.failed);
+            }
+            let _ = io::stderr().flush();
+        }
+    }
+
+    pub fn finish(&self) {
+        if self.live && self.done > 0 {
+            eprintln!();
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct ResultRow {
+    partition: i32,
+    offset: i64,
+    id: String,
+    target_topic: String,
+    status: String,
+    error: String,
+}
+
+impl From<&ActionResult> for ResultRow {
+    fn from(result: &ActionResult) -> Self {
+        Self {
+            partition: result.partition,
+            offset: result.offset,
+            id: result.id.clone().unwrap_or_else(|| "-".to_string()),
+            target_topic: result.target_topic.clone().unwrap_or_else(|| "-".to_string()),
+            status: result.status.to_string(),
+            error: result.error.clone().unwrap_or_else(|| "-".to_string()),
+        }
+    }
+}
+
+/// One row per result.
+pub fn print_results(results: &[ActionResult]) {
+    println!("{}", Table::new(results.iter().map(ResultRow::from)));
+}
+
+/// The failed results, if there are any.
+pub fn print_failures(results: &[ActionResult]) {
+    let failed: Vec<ResultRow> = results
+        .iter()
+        .filter(|result| result.error.is_some())
+        .map(ResultRow::from)
+        .collect();
+    if !failed.is_empty() {
+        println!("\nFailed ({}):", failed.len());
+        println!("{}", Table::new(failed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(selected: &[(i32, i64)], unselected: &[(i32, i64)], handled: &[(i32, i64)]) -> Vec<usize> {
+        points_from(selected, unselected, &[], handled)
+    }
+
+    fn points_from(
+        selected: &[(i32, i64)],
+        unselected: &[(i32, i64)],
+        committed: &[(i32, i64)],
+        handled: &[(i32, i64)],
+    ) -> Vec<usize> {
+        commit_points(
+            selected,
+            &unselected.iter().copied().collect(),
+            &committed.iter().copied().collect(),
+            &handled.iter().copied().collect(),
+        )
+    }
+
+    #[test]
+    fn commits_past_the_last_handled_record_of_each_partition() {
+        let selected = [(0, 0), (1, 5), (0, 1), (1, 6)];
+        assert_eq!(points(&selected, &[], &selected), [2, 3]);
+    }
+
+    #[test]
+    fn stops_at_the_first_unhandled_record() {
+        let selected = [(0, 0), (0, 1), (0, 2)];
+        assert_eq!(points(&selected, &[], &[(0, 0), (0, 2)]), [0]);
+        assert!(points(&selected, &[], &[(0, 1), (0, 2)]).is_empty());
+    }
+
+    #[test]
+    fn stops_at_the_first_unselected_record() {
+        // Offset 2 was read but not selected, or outside the time range
+        let selected = [(0, 1), (0, 3), (1, 4)];
+        assert_eq!(points(&selected, &[(0, 2)], &selected), [0, 2]);
+        assert_eq!(points(&selected, &[(1, 0)], &selected), [1]);
+        assert!(points(&selected, &[(0, 0), (1, 0)], &selected).is_empty());
+    }
+
+    #[test]
+    fn unselected_records_after_the_selection_do_not_matter() {
+        let selected = [(0, 0), (0, 1)];
+        assert_eq!(points(&selected, &[(0, 2)], &selected), [1]);
+    }
+
+    #[test]
+    fn never_moves_the_committed_offset_back() {
+        let selected = [(0, 5), (1, 5)];
+        assert_eq!(points_from(&selected, &[], &[(0, 5), (1, 6)], &selected), [0]);
+        assert!(points_from(&selected, &[], &[(0, 9), (1, 9)], &selected).is_empty());
+    }
+}
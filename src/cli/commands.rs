@@ -1,12 +1,316 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use chrono::{DateTime, TimeDelta, Utc};
 use clap::{Parser, Subcommand};
 
+use crate::{
+    cli::{JsonCondition, MessageSelector, OutputFormat},
+    filter::{lexer::parse_duration, Field, Filter, Template},
+    kafka::{Cursor, TimeRange},
+};
+
 #[derive(Parser)]
 #[command()]
 pub struct Args {
+    #[command(flatten)]
+    pub global: GlobalArgs,
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
+// Options shared by every command. Each one overrides the value from the
+// selected config profile.
+#[derive(clap::Args)]
+pub struct GlobalArgs {
+    /// Config file to load instead of the user and project config files
+    #[arg(long, global = true, value_name = "FILE", env = "POISON_QUEUE_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// Named cluster profile from the config file
+    #[arg(long, global = true, env = "POISON_QUEUE_PROFILE")]
+    pub profile: Option<String>,
+    /// Kafka bootstrap servers
+    #[arg(long, global = true, env = "POISON_QUEUE_BROKERS")]
+    pub brokers: Option<String>,
+    /// Consumer group used by commands that commit DLQ offsets
+    #[arg(long, global = true, env = "POISON_QUEUE_GROUP_ID")]
+    pub group_id: Option<String>,
+    /// Topic that archived messages are written to
+    #[arg(long, global = true, env = "POISON_QUEUE_ARCHIVE_TOPIC")]
+    pub archive_topic: Option<String>,
+    /// Schema Registry used to decode Avro payloads
+    #[arg(long, global = true, value_name = "URL", env = "POISON_QUEUE_SCHEMA_REGISTRY_URL")]
+    pub schema_registry_url: Option<String>,
+    /// Output format. Everything but `table` prints plain records for
+    /// scripts, with the fields listed in the README
+    #[arg(short, long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    pub output: OutputFormat,
+    /// Extra librdkafka property, repeatable. Prefix the key with `consumer:`
+    /// or `producer:` to apply it to one client type only
+    #[arg(
+        short = 'X',
+        long = "property",
+        global = true,
+        value_name = "KEY=VALUE",
+        value_parser = parse_property
+    )]
+    pub properties: Vec<PropertyArg>,
+}
+
+/// Which clients a `-X` property applies to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PropertyScope {
+    All,
+    Consumer,
+    Producer,
+}
+
+#[derive(Clone)]
+pub struct PropertyArg {
+    pub scope: PropertyScope,
+    pub key: String,
+    pub value: String,
+}
+
+fn parse_property(arg: &str) -> Result<PropertyArg, String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{}`", arg))?;
+
+    let (scope, key) = if let Some(key) = key.strip_prefix("consumer:") {
+        (PropertyScope::Consumer, key)
+    } else if let Some(key) = key.strip_prefix("producer:") {
+        (PropertyScope::Producer, key)
+    } else {
+        (PropertyScope::All, key)
+    };
+
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("missing property name in `{}`", arg));
+    }
+
+    Ok(PropertyArg {
+        scope,
+        key: key.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// Kafka-level and payload criteria shared by every command that selects
+/// messages. All given criteria must match.
+#[derive(clap::Args, Default)]
+pub struct FilterArgs {
+    /// Only messages whose Kafka key is KEY
+    #[arg(long)]
+    pub key: Option<String>,
+    /// Only messages with this header value, repeatable
+    #[arg(long = "header", value_name = "NAME=VALUE", value_parser = parse_header)]
+    pub headers: Vec<(String, String)>,
+    /// Only messages matching a JSON path condition, e.g.
+    /// '$.payload.userId == "user-123"'. Repeatable
+    #[arg(long = "where", value_name = "CONDITION")]
+    pub conditions: Vec<JsonCondition>,
+    /// Only messages matching a filter expression, e.g.
+    /// 'retries >= 3 and reason ~ "timeout" and moved_at > now-2d'
+    #[arg(long, value_name = "EXPR")]
+    pub filter: Option<Filter>,
+    /// Only messages with a record timestamp at or after TIME: RFC 3339
+    /// (2024-05-01T02:00:00Z) or a duration ago (30m, 6h, 2d)
+    #[arg(long, value_name = "TIME", value_parser = parse_time)]
+    pub since: Option<DateTime<Utc>>,
+    /// Only messages with a record timestamp before TIME
+    #[arg(long, value_name = "TIME", value_parser = parse_time)]
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl FilterArgs {
+    pub fn selector(&self) -> MessageSelector {
+        MessageSelector {
+            key: self.key.clone(),
+            headers: self.headers.clone(),
+            conditions: self.conditions.clone(),
+            filter: self.filter.clone(),
+            range: TimeRange {
+                since: self.since,
+                until: self.until,
+            },
+            ..Default::default()
+        }
+    }
+}
+
+fn parse_time(arg: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(arg) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    parse_duration(arg)
+        .and_then(TimeDelta::try_seconds)
+        .and_then(|ago| Utc::now().checked_sub_signed(ago))
+        .ok_or_else(|| {
+            format!("expected an RFC 3339 time or a duration such as 30m, 6h or 2d, got `{}`", arg)
+        })
+}
+
+fn parse_header(arg: &str) -> Result<(String, String), String> {
+    let (name, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got `{}`", arg))?;
+    if name.is_empty() {
+        return Err(format!("missing header name in `{}`", arg));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// The messages a command works on: an id, Kafka coordinates given as
+/// `--partition/--offset` or as a `TOPIC:PARTITION:OFFSET` locator, and/or
+/// the filters in `FilterArgs`.
+#[derive(clap::Args)]
+pub struct MessageArgs {
+    /// DLQ topic, or a TOPIC:PARTITION:OFFSET locator
+    pub topic: String,
+    /// Message ID or correlation ID to find
+    pub message_id: Option<String>,
+    /// Partition of the record, instead of a message ID
+    #[arg(long, requires = "offset", conflicts_with = "message_id")]
+    pub partition: Option<i32>,
+    /// Offset of the record, instead of a message ID
+    #[arg(long, requires = "partition")]
+    pub offset: Option<i64>,
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+impl MessageArgs {
+    /// The DLQ topic and which messages to select in it.
+    pub fn resolve(&self) -> Result<(String, MessageSelector), anyhow::Error> {
+        let mut selector = self.filter.selector();
+        selector.id = self.message_id.clone();
+
+        // Kafka topic names cannot contain ':', so a colon means a locator
+        let topic = if let Some((topic, position)) = self.topic.split_once(':') {
+            if self.message_id.is_some() || self.partition.is_some() {
+                return Err(anyhow!(
+                    "A locator cannot be combined with a message ID or --partition/--offset"
+                ));
+            }
+            let position = position
+                .split_once(':')
+                .and_then(|(partition, offset)| Some((partition.parse().ok()?, offset.parse().ok()?)))
+                .ok_or_else(|| {
+                    anyhow!("Invalid locator `{}`: expected TOPIC:PARTITION:OFFSET", self.topic)
+                })?;
+            selector.position = Some(position);
+            topic.to_string()
+        } else {
+            if let (Some(partition), Some(offset)) = (self.partition, self.offset) {
+                selector.position = Some((partition, offset));
+            }
+            self.topic.clone()
+        };
+
+        if selector.id.is_none()
+            && selector.position.is_none()
+            && selector.key.is_none()
+            && selector.headers.is_empty()
+            && selector.conditions.is_empty()
+            && selector.filter.is_none()
+            && selector.range.is_unbounded()
+        {
+            return Err(anyhow!(
+                "Give a message ID, --partition and --offset, a TOPIC:PARTITION:OFFSET locator, or --key/--header/--where/--filter/--since/--until"
+            ));
+        }
+        Ok((topic, selector))
+    }
+}
+
+/// How many of the selected messages `list-messages` shows, and in what order.
+#[derive(clap::Args)]
+pub struct PageArgs {
+    /// Show at most N messages
+    #[arg(long, value_name = "N", default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..))]
+    pub limit: u64,
+    /// Continue after the messages shown by an earlier run, as printed by it
+    #[arg(long, value_name = "CURSOR", conflicts_with_all = ["sort", "reverse"])]
+    pub cursor: Option<Cursor>,
+    /// Sort by a field instead of partition order. Scans the whole topic
+    #[arg(long, value_enum, value_name = "FIELD")]
+    pub sort: Option<SortField>,
+    /// Reverse the order: descending with --sort, otherwise the last
+    /// messages first
+    #[arg(long)]
+    pub reverse: bool,
+    /// Only print how many messages match
+    #[arg(long, conflicts_with_all = ["sort", "reverse"])]
+    pub count: bool,
+}
+
+/// Which fields list-messages prints for each message.
+#[derive(clap::Args)]
+pub struct ColumnArgs {
+    /// Show these fields instead of the default columns: envelope fields,
+    /// `partition`, `offset`, payload paths such as `payload.userId`, or
+    /// `header:NAME`
+    #[arg(long, value_name = "FIELD,...", value_delimiter = ',')]
+    pub columns: Vec<Field>,
+    /// Print one line per message, with `{FIELD}` placeholders for the
+    /// fields --columns accepts, e.g. "{id} failed: {reason}"
+    #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["columns", "count"])]
+    pub template: Option<Template>,
+}
+
+/// Options of the commands that change many messages at once.
+#[derive(clap::Args)]
+pub struct BulkArgs {
+    /// Only the messages whose IDs are listed in FILE, one per line. `-`
+    /// reads the list from stdin
+    #[arg(long, value_name = "FILE")]
+    pub ids: Option<PathBuf>,
+    /// Show the plan without changing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Options of republish-messages.
+#[derive(clap::Args)]
+pub struct RepublishArgs {
+    /// Topic to publish every message to instead of its original topic
+    #[arg(long, value_name = "TOPIC")]
+    pub target_topic: Option<String>,
+    #[command(flatten)]
+    pub limits: ProduceLimits,
+    /// Do not ask for confirmation
+    #[arg(long, short)]
+    pub yes: bool,
+}
+
+/// How fast records are produced.
+#[derive(clap::Args, Clone, Copy)]
+pub struct ProduceLimits {
+    /// Produce at most N messages per second
+    #[arg(long, value_name = "N", value_parser = parse_rate)]
+    pub rate: Option<f64>,
+    /// Messages that may wait for their delivery report at once
+    #[arg(long, value_name = "N", default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_in_flight: u32,
+}
+
+fn parse_rate(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        _ => Err(format!("expected a positive number of messages per second, got `{}`", arg)),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortField {
+    #[value(name = "moved_at")]
+    MovedAt,
+    Retries,
+    Reason,
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
     /// List Topics.
@@ -14,16 +318,48 @@ pub enum Commands {
     /// List Messages in a topic.
     ListMessages {
         topic: String,
+        /// Start at the consumer group's committed offsets to show only unhandled messages
+        #[arg(long)]
+        from_committed: bool,
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[command(flatten)]
+        page: PageArgs,
+        #[command(flatten)]
+        columns: ColumnArgs,
     },
-    /// List a message.
+    /// Show the messages matching a selection.
     ViewMessage {
-        topic: String,
-        message_id: String,
+        #[command(flatten)]
+        message: MessageArgs,
+        /// Only search messages after the consumer group's committed offsets
+        #[arg(long)]
+        from_committed: bool,
+        /// Show the payload as a hex dump
+        #[arg(long)]
+        hex: bool,
     },
     /// Archive a message.
     ArchiveMessage {
+        #[command(flatten)]
+        message: MessageArgs,
+    },
+    /// Archive every message matching a filter or an ID list.
+    ///
+    /// Scans the DLQ once and shows how many messages will be archived, by
+    /// reason and original topic, then archives them all and commits the
+    /// consumer group past them.
+    ArchiveMessages {
         topic: String,
-        message_id: String,
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[command(flatten)]
+        bulk: BulkArgs,
+    },
+    /// Remove a message from the DLQ without archiving or republishing it.
+    DiscardMessage {
+        #[command(flatten)]
+        message: MessageArgs,
     },
     /// Republish a DLQ message to its original topic.
     /// 
@@ -31,10 +367,8 @@ pub enum Commands {
     /// version from a JSON file, shows a diff preview, then publishes to the original
     /// topic and removes the message from the DLQ.
     RepublishMessage {
-        /// DLQ topic to read from
-        topic: String,
-        /// Message ID or correlation ID to find
-        message_id: String,
+        #[command(flatten)]
+        message: MessageArgs,
         /// Path to JSON file with fixed payload (optional)
         /// If not provided, republishes the original payload as-is
         #[arg(long, value_name = "FILE", help = "Path to fixed payload file")]
@@ -42,5 +376,23 @@ pub enum Commands {
         /// Preview changes without publishing (dry run) (default: false)
         #[arg(long, default_value = "false")]
         dry_run: bool,
+        /// Topic to publish to instead of the original topic. Required when
+        /// the DLQ record does not say where it came from.
+        #[arg(long, value_name = "TOPIC")]
+        target_topic: Option<String>,
+    },
+    /// Republish every message matching a filter or an ID list.
+    ///
+    /// Scans the DLQ once, shows how many messages will be republished and
+    /// asks once for confirmation, then republishes them to their original
+    /// topics and commits the consumer group past them.
+    RepublishMessages {
+        topic: String,
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[command(flatten)]
+        bulk: BulkArgs,
+        #[command(flatten)]
+        republish: RepublishArgs,
     },
 }
\ No newline at end of file
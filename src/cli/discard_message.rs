@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+
+use anyhow::bail;
+use colored::Colorize;
+use tabled::{settings::Rotate, Table};
+
+use crate::{
+    cli::{
+        confirm_action, print_records, Action, ActionResult, ActionStatus, BulkSelection,
+        MessageSelector, OutputFormat, UnparseableRecord,
+    },
+    kafka::{ParseFailurePolicy, ScanMode},
+    settings::Settings,
+};
+
+pub async fn discard_message(
+    settings: &Settings,
+    topic: &str,
+    selector: &MessageSelector,
+    output: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    let mut scanner = selector
+        .scanner(settings, topic, ScanMode::Group)?
+        .on_parse_failure(ParseFailurePolicy::Keep);
+
+    let selection = BulkSelection::scan(&mut scanner, selector, None).await?;
+    let record = selection.single(&scanner)?;
+    // Committing is the discard, so it must not take other records with it
+    if let Some(reason) = selection.uncommittable(record) {
+        bail!(
+            "Cannot discard partition {} offset {}: {}",
+            record.partition,
+            record.offset,
+            reason
+        );
+    }
+
+    if output.is_table() {
+        let mut table = if record.parse_error.is_some() {
+            Table::new(vec![UnparseableRecord::from(record)])
+        } else {
+            Table::new(vec![record.message.clone()])
+        };
+        table.with(Rotate::Left);
+        println!("{table}");
+    }
+
+    eprintln!(
+        "\n{} Partition {} offset {} will be removed from {} without being archived.",
+        "WARNING:".red().bold(),
+        record.partition,
+        record.offset,
+        topic.yellow()
+    );
+    if !confirm_action()? {
+        if output.is_table() {
+            println!("Operation cancelled.");
+        } else {
+            print_records(output, ActionResult::FIELDS, &[ActionResult::new(Action::Discard, ActionStatus::Cancelled, record, None)])?;
+        }
+        return Ok(());
+    }
+
+    selection.commit(&scanner, &HashSet::from([(record.partition, record.offset)]))?;
+    if output.is_table() {
+        println!("✅ Discarded partition {} offset {} from {}", record.partition, record.offset, topic);
+    } else {
+        print_records(output, ActionResult::FIELDS, &[ActionResult::new(Action::Discard, ActionStatus::Discarded, record, None)])?;
+    }
+    Ok(())
+}
@@ -1,80 +1,216 @@
-use anyhow::{anyhow, Context};
-use log::{info, warn};
-use rdkafka::{config::RDKafkaLogLevel, consumer::Consumer, ClientConfig, Message};
-use serde_json::{from_str};
-use tabled::{Table, Tabled};
+use std::{cmp::Ordering, collections::VecDeque};
 
-use crate::{cli::{FIELD_CORRELATION_ID, FIELD_ID, FIELD_METADATA, METADATA_FAILURE_REASON, METADATA_MOVED_TO_DLQ_AT, METADATA_ORIGINAL_TOPIC, METADATA_RETRY_COUNT}, kafka::{CustomContext, LoggingConsumer, AUTO_OFFSET_RESET, BOOTSTRAP_SERVERS, ENABLE_AUTO_COMMIT, ENABLE_PARTITION_EOF, GROUP_ID, SESSION_TIMEOUT_MS, TIMEOUT_MS}};
+use anyhow::bail;
+use serde_json::{json, Map, Value};
+use tabled::{builder::Builder, Table, Tabled};
+
+use crate::{
+    cli::{print_records, ColumnArgs, DlqMessage, MessageSelector, OutputFormat, PageArgs, SortField, UnparseableRecord},
+    envelope,
+    filter::{parse_time, Field},
+    kafka::{DlqRecord, ScanMode},
+    settings::Settings,
+};
 
 pub async fn list_messages(
-    brokers: &str,
-    group_id: &str,
+    settings: &Settings,
     topic: &str,
-    // assignor: Option<&String>,
+    from_committed: bool,
+    selector: &MessageSelector,
+    page: &PageArgs,
+    columns: &ColumnArgs,
+    output: OutputFormat,
 ) -> Result<(), anyhow::Error> {
-    let context = CustomContext;
-
-    let mut config = ClientConfig::new();
-
-    config
-        .set(GROUP_ID, group_id)
-        .set(BOOTSTRAP_SERVERS, brokers)
-        .set(ENABLE_PARTITION_EOF, "true")  // Enable EOF detection
-        .set(AUTO_OFFSET_RESET, "earliest") // Start from beginning
-        .set(ENABLE_AUTO_COMMIT, "false")   // Don't auto-commit
-        .set(SESSION_TIMEOUT_MS, TIMEOUT_MS)
-        .set_log_level(RDKafkaLogLevel::Debug);
-
-    let consumer: LoggingConsumer = config
-        .create_with_context(context)
-        .context("Consumer creation failed")?;
-
-    let topics = &[topic];
-    consumer
-        .subscribe(topics)
-        .map_err(|e| anyhow!("Failed to subscribe to topic: {}", e))?;
-
-    let mut table_data:Vec<MessageListItem> = vec!();
-    loop {
-        match consumer.recv().await {
-            Err(e) => {
-                if let rdkafka::error::KafkaError::PartitionEOF(_) = e {
-                    info!("Reached end of partition (EOF)");
-                    break;
-                } else {
-                    return Err(anyhow!("Kafka error: {}", e));
+    let (columns, template) = (columns.columns.as_slice(), columns.template.as_ref());
+    if template.is_some() && !output.is_table() {
+        bail!("--template prints text and cannot be combined with --output");
+    }
+    let mode = if from_committed { ScanMode::FromCommitted } else { ScanMode::Browse };
+    let selector = MessageSelector {
+        cursor: page.cursor.clone(),
+        ..selector.clone()
+    };
+    let mut scanner = selector.scanner(settings, topic, mode)?;
+    let mapping = scanner.mapping().clone();
+    let limit = page.limit as usize;
+
+    let mut rows = Rows::new(page.sort, page.reverse, limit);
+    let mut unparseable: Vec<DlqRecord> = vec![];
+    let (mut matched, mut unparseable_count) = (0, 0);
+    while let Some(record) = scanner.next().await? {
+        if !selector.matches(&record, &mapping) {
+            continue;
+        }
+        if record.parse_error.is_some() {
+            unparseable_count += 1;
+            if !page.count && unparseable.len() < limit {
+                unparseable.push(record);
+            }
+            continue;
+        }
+
+        matched += 1;
+        if page.count {
+            continue;
+        }
+        rows.push(record);
+        // In partition order the first `limit` messages are all there is to show
+        if page.sort.is_none() && !page.reverse && matched >= limit {
+            break;
+        }
+    }
+
+    let more = page.sort.is_none() && !page.reverse && matched >= limit;
+    let cursor = if more { scanner.cursor() } else { None };
+    // Unparseable records past the page are not counted
+    let scope = if cursor.is_some() { " in this page" } else { "" };
+
+    // Machine formats print records only; the rest goes to stderr
+    if !output.is_table() {
+        if page.count {
+            print_records(
+                output,
+                &["messages", "unparseable"],
+                &[json!({ "messages": matched, "unparseable": unparseable_count })],
+            )?;
+        } else {
+            let records: Vec<Value> = rows
+                .into_items()
+                .iter()
+                .chain(&unparseable)
+                .map(|record| envelope::document(record, &mapping))
+                .map(|document| match columns {
+                    [] => document,
+                    _ => Value::Object(
+                        columns
+                            .iter()
+                            .map(|field| (field.name().to_string(), field.value(&document)))
+                            .collect::<Map<_, _>>(),
+                    ),
+                })
+                .collect();
+            let fields: Vec<&str> = match columns {
+                [] => envelope::DOCUMENT_FIELDS.to_vec(),
+                _ => columns.iter().map(Field::name).collect(),
+            };
+            print_records(output, &fields, &records)?;
+        }
+        eprintln!("{}: {}", topic, scanner.summary());
+        if let Some(cursor) = cursor {
+            eprintln!("More messages may follow: --cursor {}", cursor);
+        }
+        return Ok(());
+    }
+
+    if page.count {
+        println!("{} message(s), {} unparseable", matched, unparseable_count);
+        println!("{}: {}", topic, scanner.summary());
+        return Ok(());
+    }
+
+    // One line per message on stdout, so the rest goes to stderr
+    if let Some(template) = template {
+        for record in rows.into_items() {
+            println!("{}", template.render(&envelope::document(&record, &mapping)));
+        }
+        if unparseable_count > 0 {
+            eprintln!("{} unparseable record(s){} not shown", unparseable_count, scope);
+        }
+        eprintln!("{}: {}", topic, scanner.summary());
+        if let Some(cursor) = cursor {
+            eprintln!("More messages may follow: --cursor {}", cursor);
+        }
+        return Ok(());
+    }
+
+    let shown = rows.len();
+    let table = if columns.is_empty() {
+        Table::new(rows.into_items().into_iter().map(|record| MessageListItem::from(record.message)))
+    } else {
+        let mut builder = Builder::default();
+        builder.push_record(columns.iter().map(Field::name));
+        for record in rows.into_items() {
+            let document = envelope::document(&record, &mapping);
+            builder.push_record(columns.iter().map(|field| field.text(&document).unwrap_or_else(|| "-".to_string())));
+        }
+        builder.build()
+    };
+    println!("{table}");
+    if page.sort.is_some() || page.reverse {
+        println!("Showing {} of {} message(s)", shown, matched);
+    }
+    if unparseable_count > 0 {
+        println!("\nUnparseable records{} ({}):", scope, unparseable_count);
+        println!("{}", Table::new(unparseable.iter().map(UnparseableRecord::from)));
+        println!("View them with `view-message {} --partition <P> --offset <O>`", topic);
+    }
+    println!("{}: {}", topic, scanner.summary());
+
+    if let Some(cursor) = cursor {
+        println!("More messages may follow: list-messages {} --cursor {}", topic, cursor);
+    }
+    Ok(())
+}
+
+/// The messages to show, never more than the limit. Unsorted, they are the
+/// first (or with `reverse`, the last) messages scanned; sorted, the
+/// smallest (or largest) by the sort field.
+struct Rows {
+    sort: Option<SortField>,
+    reverse: bool,
+    limit: usize,
+    items: VecDeque<DlqRecord>,
+}
+
+impl Rows {
+    fn new(sort: Option<SortField>, reverse: bool, limit: usize) -> Self {
+        Self { sort, reverse, limit, items: VecDeque::new() }
+    }
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    fn push(&mut self, record: DlqRecord) {
+        match self.sort {
+            None => {
+                self.items.push_back(record);
+                if self.items.len() > self.limit {
+                    self.items.pop_front();
                 }
             }
-            Ok(m) => {
-                let payload = match m.payload_view::<str>() {
-                    None => {
-                        warn!("No payload found in message, skipping");
-                        continue;
-                    },
-                    Some(Ok(s)) => s,
-                    Some(Err(e)) => {
-                        warn!("Error while deserializing message payload: {:?}", e);
-                        ""
-                    }
-                };
-                
-                let json: serde_json::Value = match from_str(payload) {
-                    Ok(j) => j,
-                    Err(e) => {
-                        warn!("Failed to parse message as JSON: {}, skipping", e);
-                        continue;
-                    }
-                };
-
-                let item = MessageListItem::parse(json);
-                table_data.push(item);
+            Some(field) => {
+                // After equal messages, so ties keep scan order
+                let index = self
+                    .items
+                    .partition_point(|item| self.compare(&item.message, &record.message, field) != Ordering::Greater);
+                if index < self.limit {
+                    self.items.insert(index, record);
+                    self.items.truncate(self.limit);
+                }
             }
+        }
+    }
+
+    fn compare(&self, a: &DlqMessage, b: &DlqMessage, field: SortField) -> Ordering {
+        let ordering = match field {
+            SortField::MovedAt => match (parse_time(&a.moved_at), parse_time(&b.moved_at)) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => a.moved_at.cmp(&b.moved_at),
+            },
+            SortField::Retries => a.retries.cmp(&b.retries),
+            SortField::Reason => a.reason.cmp(&b.reason),
         };
+        if self.reverse { ordering.reverse() } else { ordering }
     }
 
-    let table = Table::new(table_data);
-    println!("{table}");
-    Ok(())
+    fn into_items(self) -> Vec<DlqRecord> {
+        if self.sort.is_none() && self.reverse {
+            self.items.into_iter().rev().collect()
+        } else {
+            self.items.into()
+        }
+    }
 }
 
 #[derive(Tabled)]
@@ -84,25 +220,87 @@ struct MessageListItem {
     reason: String,
     retries: i64,
     original_topic: String,
+    #[tabled(rename = "type")]
+    event_type: String,
+    #[tabled(rename = "source")]
+    event_source: String,
     moved_at: String,
 }
 
-impl MessageListItem {
-    fn parse(json: serde_json::Value) -> Self {
-        let id = json[FIELD_ID].as_str().unwrap_or("-");
-        let correlation_id = json[FIELD_CORRELATION_ID].as_str().unwrap_or("-");
-        let reason = json[FIELD_METADATA][METADATA_FAILURE_REASON].as_str().unwrap_or("-");
-        let retries = json[FIELD_METADATA][METADATA_RETRY_COUNT].as_i64().unwrap_or(0);
-        let original = json[FIELD_METADATA][METADATA_ORIGINAL_TOPIC].as_str().unwrap_or("-");
-        let moved_at = json[FIELD_METADATA][METADATA_MOVED_TO_DLQ_AT].as_str().unwrap_or("-");
-
+impl From<DlqMessage> for MessageListItem {
+    fn from(message: DlqMessage) -> Self {
         Self {
-            id: id.to_string(),
-            correlation_id: correlation_id.to_string(),
-            reason: reason.to_string(),
-            retries,
-            original_topic: original.to_string(),
-            moved_at: moved_at.to_string(),
+            id: message.id,
+            correlation_id: message.correlation_id,
+            reason: message.reason,
+            retries: message.retries,
+            original_topic: message.original_topic,
+            event_type: message.event_type.unwrap_or_else(|| "-".to_string()),
+            event_source: message.event_source.unwrap_or_else(|| "-".to_string()),
+            moved_at: message.moved_at,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::envelope::EnvelopeFormat;
+
+    fn record(offset: i64, retries: i64) -> DlqRecord {
+        DlqRecord {
+            topic: "dlq".to_string(),
+            partition: 0,
+            offset,
+            key: None,
+            headers: vec![],
+            timestamp: None,
+            payload: None,
+            format: EnvelopeFormat::Json,
+            envelope: Value::Null,
+            payload_encoding: None,
+            parse_error: None,
+            message: DlqMessage { retries, ..Default::default() },
         }
     }
-}
\ No newline at end of file
+
+    /// Offsets of the rows kept after pushing `(offset, retries)` in order.
+    fn kept(sort: Option<SortField>, reverse: bool, limit: usize, records: &[(i64, i64)]) -> Vec<i64> {
+        let mut rows = Rows::new(sort, reverse, limit);
+        for (offset, retries) in records {
+            rows.push(record(*offset, *retries));
+        }
+        assert!(rows.len() <= limit);
+        rows.into_items().iter().map(|record| record.offset).collect()
+    }
+
+    #[test]
+    fn unsorted_keeps_the_first_rows() {
+        // The scan stops at the limit, so more are never pushed
+        assert_eq!(kept(None, false, 3, &[(0, 0), (1, 0), (2, 0)]), [0, 1, 2]);
+        assert_eq!(kept(None, false, 3, &[(0, 0)]), [0]);
+    }
+
+    #[test]
+    fn reverse_keeps_the_last_rows_last_first() {
+        let records: Vec<(i64, i64)> = (0..10).map(|offset| (offset, 0)).collect();
+        assert_eq!(kept(None, true, 3, &records), [9, 8, 7]);
+        assert_eq!(kept(None, true, 3, &records[..2]), [1, 0]);
+    }
+
+    #[test]
+    fn sorted_keeps_the_smallest_rows() {
+        let records = [(0, 5), (1, 1), (2, 9), (3, 3), (4, 0), (5, 7)];
+        assert_eq!(kept(Some(SortField::Retries), false, 3, &records), [4, 1, 3]);
+        assert_eq!(kept(Some(SortField::Retries), true, 3, &records), [2, 5, 0]);
+        assert_eq!(kept(Some(SortField::Retries), false, 10, &records), [4, 1, 3, 0, 5, 2]);
+    }
+
+    #[test]
+    fn sorted_ties_keep_scan_order() {
+        let records = [(0, 1), (1, 0), (2, 1), (3, 0), (4, 1)];
+        assert_eq!(kept(Some(SortField::Retries), false, 4, &records), [1, 3, 0, 2]);
+        assert_eq!(kept(Some(SortField::Retries), true, 4, &records), [0, 2, 4, 1]);
+    }
+}
@@ -1,32 +1,31 @@
 use std::time::Duration;
 
-use anyhow::{anyhow};
-use rdkafka::{consumer::Consumer, ClientConfig};
+use anyhow::Context;
+use log::warn;
+use rdkafka::consumer::Consumer;
+use serde::Serialize;
 
-use crate::kafka::{CustomContext, LoggingConsumer};
+use crate::{
+    cli::{print_records, OutputFormat},
+    envelope::EnvelopeFormat,
+    kafka::{consumer_config, CustomContext, DlqScanner, LoggingConsumer, ScanMode},
+    settings::Settings,
+};
 
-const DEAD_LETTER_QUEUE_PREFIX: &str = "dlq-";
-
-pub async fn list_topics(brokers: &str,) -> Result<(), anyhow::Error> {
+pub async fn list_topics(settings: &Settings, output: OutputFormat) -> Result<(), anyhow::Error> {
     let context = CustomContext;
 
-    let mut config = ClientConfig::new();
-    config
-        .set("bootstrap.servers", brokers)
-        .set("group.id", "topic-lister")
-        .set("session.timeout.ms", "6000");
-    
-    let consumer: LoggingConsumer = config
+    // Metadata requests need no consumer group
+    let consumer: LoggingConsumer = consumer_config(settings)
         .create_with_context(context)
-        .expect("Consumer creation failed");
+        .context("Consumer creation failed")?;
     
     // Fetch metadata for all topics (pass None to get all topics)
     let metadata = consumer
         .fetch_metadata(None, Duration::from_secs(10))
-        .map_err(|e| anyhow!("Failed to fetch Kafka metadata: {:?}", e))
-        .expect("Unable to fetch Kafka metadata");
+        .context("Failed to fetch Kafka metadata")?;
 
-    if metadata.topics().is_empty() {
+    if metadata.topics().is_empty() && output.is_table() {
         println!("🔍 No topics found in Kafka cluster");
         return Ok(());
     }
@@ -47,31 +46,52 @@ pub async fn list_topics(brokers: &str,) -> Result<(), anyhow::Error> {
         // Kafka's rdkafka does not expose is_internal; use naming convention if needed
         let is_internal = topic_name.starts_with("__");
 
+        let is_dlq = !is_internal && settings.is_dlq_topic(topic_name);
+
         let topic_info = TopicDisplayInfo {
             name: topic_name.to_string(),
             partitions,
             replication_factor,
             is_internal,
+            is_dlq,
+            format: None,
         };
 
         if is_internal {
             internal_topics.push(topic_info);
-        } else if topic_name.starts_with(DEAD_LETTER_QUEUE_PREFIX) {
+        } else if is_dlq {
             dlq_topics.push(topic_info);
         } else {
             regular_topics.push(topic_info);
         }
     }
     
+    for topic in &mut dlq_topics {
+        topic.format = Some(envelope_format(settings, &topic.name).await);
+    }
+
     // Sort each category
     regular_topics.sort_by(|a, b| a.name.cmp(&b.name));
     dlq_topics.sort_by(|a, b| a.name.cmp(&b.name));
     internal_topics.sort_by(|a, b| a.name.cmp(&b.name));
+
+    if !output.is_table() {
+        let rows: Vec<TopicRecord> = regular_topics
+            .iter()
+            .chain(&dlq_topics)
+            .chain(&internal_topics)
+            .map(TopicRecord::from)
+            .collect();
+        return print_records(output, TopicRecord::FIELDS, &rows);
+    }
     
     // Print header
     println!("📋 Kafka Topics Overview");
     println!("{}", "═".repeat(70));
-    println!("🔗 Broker: {}", brokers);
+    if let Some(profile) = &settings.profile {
+        println!("👤 Profile: {}", profile);
+    }
+    println!("🔗 Broker: {}", settings.brokers);
     println!("📊 Total Topics: {}", metadata.topics().len());
     println!();
     
@@ -116,28 +136,120 @@ struct TopicDisplayInfo {
     partitions: usize,
     replication_factor: usize,
     is_internal: bool,
+    is_dlq: bool,
+    /// Envelope format, only looked up for DLQ topics
+    format: Option<TopicFormat>,
+}
+
+#[derive(Debug)]
+struct TopicFormat {
+    /// `None` for an empty topic, or when detection failed
+    format: Option<EnvelopeFormat>,
+    pinned: bool,
+    failed: bool,
+}
+
+impl std::fmt::Display for TopicFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.format {
+            Some(format) if self.pinned => write!(f, "{} (pinned)", format),
+            Some(format) => write!(f, "{}", format),
+            None if self.failed => f.write_str("?"),
+            None => f.write_str("-"),
+        }
+    }
+}
+
+/// A topic in machine-readable output.
+#[derive(Serialize)]
+struct TopicRecord {
+    name: String,
+    /// `regular`, `dlq` or `internal`
+    #[serde(rename = "type")]
+    topic_type: &'static str,
+    partitions: usize,
+    replication_factor: usize,
+    /// Envelope format of a DLQ topic, if known
+    format: Option<EnvelopeFormat>,
+    format_pinned: bool,
+}
+
+impl TopicRecord {
+    const FIELDS: &'static [&'static str] =
+        &["name", "type", "partitions", "replication_factor", "format", "format_pinned"];
+}
+
+impl From<&TopicDisplayInfo> for TopicRecord {
+    fn from(topic: &TopicDisplayInfo) -> Self {
+        Self {
+            name: topic.name.clone(),
+            topic_type: if topic.is_internal {
+                "internal"
+            } else if topic.is_dlq {
+                "dlq"
+            } else {
+                "regular"
+            },
+            partitions: topic.partitions,
+            replication_factor: topic.replication_factor,
+            format: topic.format.as_ref().and_then(|format| format.format),
+            format_pinned: topic.format.as_ref().is_some_and(|format| format.pinned),
+        }
+    }
+}
+
+/// The format a DLQ topic is decoded with.
+async fn envelope_format(settings: &Settings, topic: &str) -> TopicFormat {
+    if let Some(format) = settings.envelope_format(topic) {
+        return TopicFormat { format: Some(format), pinned: true, failed: false };
+    }
+
+    let detected = match DlqScanner::new(settings, topic, ScanMode::Browse) {
+        Ok(mut scanner) => scanner.format().await,
+        Err(e) => Err(e),
+    };
+    match detected {
+        Ok(format) => TopicFormat { format, pinned: false, failed: false },
+        Err(e) => {
+            warn!("Could not detect the format of {}: {:#}", topic, e);
+            TopicFormat { format: None, pinned: false, failed: true }
+        }
+    }
 }
 
 fn print_topic_table(topics: &[TopicDisplayInfo], icon: &str) {
+    let show_format = topics.iter().any(|topic| topic.format.is_some());
+
     // Print table header
-    println!("{:<35} {:<12} {:<8} {:<10}", "TOPIC NAME", "TYPE", "PARTS", "REPLICATION");
+    print!("{:<35} {:<12} {:<8} {:<12}", "TOPIC NAME", "TYPE", "PARTS", "REPLICATION");
+    if show_format {
+        print!(" FORMAT");
+    }
+    println!();
     println!("{}", "─".repeat(70));
     
     // Print each topic
     for topic in topics {
         let topic_type = if topic.is_internal {
             "Internal"
-        } else if topic.name.starts_with("dlq-") || topic.name.contains("-dlq") {
+        } else if topic.is_dlq {
             "DLQ"
         } else {
             "Regular"
         };
         
-        println!("{:<35} {:<12} {:<8} {:<10}", 
+        print!("{:<35} {:<12} {:<8} {:<12}", 
             format!("{} {}", icon, topic.name),
             topic_type,
             topic.partitions,
             topic.replication_factor
         );
+        if show_format {
+            match &topic.format {
+                Some(format) => print!(" {}", format),
+                None => print!(" -"),
+            }
+        }
+        println!();
     }
 }
\ No newline at end of file
@@ -3,13 +3,25 @@ pub mod list_topics;
 pub mod list_messages;
 pub mod view_message_by_id;
 pub mod archive_message;
+pub mod archive_messages;
+pub mod bulk;
+pub mod discard_message;
 pub mod model;
+pub mod output;
 pub mod republish_message;
+pub mod republish_messages;
+pub mod selector;
 
 pub use commands::*;
 pub use list_topics::*;
 pub use list_messages::*;
 pub use view_message_by_id::*;
 pub use archive_message::*;
+pub use archive_messages::*;
+pub use bulk::*;
+pub use discard_message::*;
 pub use model::*;
-pub use republish_message::*;
\ No newline at end of file
+pub use output::*;
+pub use republish_message::*;
+pub use republish_messages::*;
+pub use selector::*;
\ No newline at end of file
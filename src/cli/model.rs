@@ -1,6 +1,12 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use tabled::Tabled;
 
+use crate::{
+    kafka::DlqRecord,
+    settings::{EnvelopeFields, RawFields},
+};
+
 // DLQ message field names
 pub const FIELD_ID: &str = "id";
 pub const FIELD_CORRELATION_ID: &str = "correlationId";
@@ -9,16 +15,123 @@ pub const FIELD_METADATA: &str = "metadata";
 
 // Metadata field names
 pub const METADATA_ARCHIVED_AT: &str = "archivedAt";
+pub const METADATA_ORIGINAL_TIMESTAMP: &str = "originalTimestamp";
 pub const METADATA_FAILURE_REASON: &str = "failureReason";
 pub const METADATA_RETRY_COUNT: &str = "retryCount";
 pub const METADATA_ORIGINAL_TOPIC: &str = "originalTopic";
 pub const METADATA_MOVED_TO_DLQ_AT: &str = "movedToDlqAt";
 
-#[derive(Tabled, Serialize, Deserialize)]
+/// JSON Pointers locating each envelope field in a DLQ message. The default
+/// mapping follows the layout described in the README.
+#[derive(Debug, Clone)]
+pub struct EnvelopeMapping {
+    pub id: String,
+    pub correlation_id: String,
+    pub payload: String,
+    pub metadata: String,
+    pub reason: String,
+    pub retries: String,
+    pub original_topic: String,
+    pub moved_at: String,
+    pub archived_at: String,
+    /// Where archiving records the DLQ record's Kafka timestamp
+    pub original_timestamp: String,
+    /// Headers read in raw mode
+    pub raw: RawFields,
+}
+
+impl Default for EnvelopeMapping {
+    fn default() -> Self {
+        let metadata = |field: &str| format!("/{}/{}", FIELD_METADATA, field);
+        Self {
+            id: format!("/{}", FIELD_ID),
+            correlation_id: format!("/{}", FIELD_CORRELATION_ID),
+            payload: format!("/{}", FIELD_PAYLOAD),
+            metadata: format!("/{}", FIELD_METADATA),
+            reason: metadata(METADATA_FAILURE_REASON),
+            retries: metadata(METADATA_RETRY_COUNT),
+            original_topic: metadata(METADATA_ORIGINAL_TOPIC),
+            moved_at: metadata(METADATA_MOVED_TO_DLQ_AT),
+            archived_at: metadata(METADATA_ARCHIVED_AT),
+            original_timestamp: metadata(METADATA_ORIGINAL_TIMESTAMP),
+            raw: RawFields::default(),
+        }
+    }
+}
+
+impl EnvelopeMapping {
+    /// Default mapping with the configured fields replaced.
+    pub fn from_fields(fields: EnvelopeFields, raw: RawFields) -> Self {
+        let default = Self::default();
+        Self {
+            id: fields.id.unwrap_or(default.id),
+            correlation_id: fields.correlation_id.unwrap_or(default.correlation_id),
+            payload: fields.payload.unwrap_or(default.payload),
+            metadata: fields.metadata.unwrap_or(default.metadata),
+            reason: fields.reason.unwrap_or(default.reason),
+            retries: fields.retries.unwrap_or(default.retries),
+            original_topic: fields.original_topic.unwrap_or(default.original_topic),
+            moved_at: fields.moved_at.unwrap_or(default.moved_at),
+            archived_at: fields.archived_at.unwrap_or(default.archived_at),
+            original_timestamp: fields.original_timestamp.unwrap_or(default.original_timestamp),
+            raw,
+        }
+    }
+
+    /// String at `pointer`, or `-` when it is missing. Numbers are rendered
+    /// as text so that numeric ids still match.
+    pub fn text(json: &Value, pointer: &str) -> String {
+        match json.pointer(pointer) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Number(n)) => n.to_string(),
+            _ => "-".to_string(),
+        }
+    }
+
+    /// Integer at `pointer`, accepting numeric strings; 0 when missing.
+    pub fn integer(json: &Value, pointer: &str) -> i64 {
+        match json.pointer(pointer) {
+            Some(Value::Number(n)) => n.as_i64().unwrap_or(0),
+            Some(Value::String(s)) => s.trim().parse().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    /// Set the value at `pointer`, creating intermediate objects as needed.
+    pub fn set(json: &mut Value, pointer: &str, value: Value) {
+        if pointer.is_empty() {
+            *json = value;
+            return;
+        }
+
+        let mut target = json;
+        for token in pointer[1..].split('/') {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            if !target.is_object() {
+                *target = Value::Object(Default::default());
+            }
+            target = target
+                .as_object_mut()
+                .expect("target was just made an object")
+                .entry(token)
+                .or_insert(Value::Null);
+        }
+        *target = value;
+    }
+}
+
+#[derive(Tabled, Serialize, Deserialize, Debug, Clone, Default)]
+#[tabled(display(Option, "tabled::derive::display::option", "-"))]
 pub struct DlqMessage {
     pub payload: String,
     pub metadata: String,
     pub moved_at: String,
+    pub event_source: Option<String>,
+    pub event_type: Option<String>,
+    pub task: Option<String>,
+    pub connector: Option<String>,
+    pub original_offset: Option<i64>,
+    pub original_partition: Option<i32>,
     pub original_topic: String,
     pub retries: i64,
     pub reason: String,
@@ -27,28 +140,58 @@ pub struct DlqMessage {
 }
 
 impl DlqMessage {
-    pub fn parse(json: serde_json::Value) -> Self {
-        let id = json[FIELD_ID].as_str().unwrap_or("-");
-        let correlation_id = json[FIELD_CORRELATION_ID].as_str().unwrap_or("-");
-        let reason = json[FIELD_METADATA][METADATA_FAILURE_REASON].as_str().unwrap_or("-");
-        let retries = json[FIELD_METADATA][METADATA_RETRY_COUNT].as_i64().unwrap_or(0);
-        let original = json[FIELD_METADATA][METADATA_ORIGINAL_TOPIC].as_str().unwrap_or("-");
-        let moved_at = json[FIELD_METADATA][METADATA_MOVED_TO_DLQ_AT].as_str().unwrap_or("-");
-
-        let metadata = serde_json::to_string_pretty(&json[FIELD_METADATA])
+    pub fn parse(json: serde_json::Value, mapping: &EnvelopeMapping) -> Self {
+        let id = EnvelopeMapping::text(&json, &mapping.id);
+        let correlation_id = EnvelopeMapping::text(&json, &mapping.correlation_id);
+        let reason = EnvelopeMapping::text(&json, &mapping.reason);
+        let retries = EnvelopeMapping::integer(&json, &mapping.retries);
+        let original = EnvelopeMapping::text(&json, &mapping.original_topic);
+        let moved_at = EnvelopeMapping::text(&json, &mapping.moved_at);
+
+        let metadata = json.pointer(&mapping.metadata).unwrap_or(&Value::Null);
+        let metadata = serde_json::to_string_pretty(metadata)
             .unwrap_or_else(|_| "-".to_string());
-        let payload = serde_json::to_string_pretty(&json[FIELD_PAYLOAD])
+        let payload = json.pointer(&mapping.payload).unwrap_or(&Value::Null);
+        let payload = serde_json::to_string_pretty(payload)
             .unwrap_or_else(|_| "-".to_string());
         
         Self {
-            id: id.to_string(),
-            correlation_id: correlation_id.to_string(),
-            reason: reason.to_string(),
+            id,
+            correlation_id,
+            reason,
             retries,
-            original_topic: original.to_string(),
-            moved_at: moved_at.to_string(),
-            metadata: metadata.to_string(),
-            payload: payload.to_string(),
+            original_topic: original,
+            original_partition: None,
+            original_offset: None,
+            moved_at,
+            connector: None,
+            task: None,
+            event_type: None,
+            event_source: None,
+            metadata,
+            payload,
+        }
+    }
+}
+
+/// A DLQ record that could not be decoded, identified by its coordinates.
+#[derive(Tabled, Debug, Clone)]
+pub struct UnparseableRecord {
+    pub partition: i32,
+    pub offset: i64,
+    pub key: String,
+    pub size: usize,
+    pub error: String,
+}
+
+impl From<&DlqRecord> for UnparseableRecord {
+    fn from(record: &DlqRecord) -> Self {
+        Self {
+            partition: record.partition,
+            offset: record.offset,
+            key: record.key_text().unwrap_or_else(|| "-".to_string()),
+            size: record.payload.as_ref().map_or(0, Vec::len),
+            error: record.parse_error.clone().unwrap_or_else(|| "-".to_string()),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,318 @@
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::kafka::DlqRecord;
+
+/// How commands print their results. `Table` is for people; the other
+/// formats print one record per topic, message or action, with the fields
+/// documented in the README, and no colors or decoration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Ndjson,
+    Csv,
+    Yaml,
+}
+
+impl OutputFormat {
+    pub fn is_table(self) -> bool {
+        self == OutputFormat::Table
+    }
+}
+
+/// What a command that changes the DLQ did, or would do, with one record.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionResult {
+    pub action: Action,
+    pub status: ActionStatus,
+    /// DLQ topic and coordinates of the record
+    pub topic: String,
+    pub partition: i32,
+    pub offset: i64,
+    /// Message id, unless the record could not be decoded
+    pub id: Option<String>,
+    /// Where the record is (or would be) written: the archive topic, or the
+    /// topic it is republished to
+    pub target_topic: Option<String>,
+    /// Why the action failed
+    pub error: Option<String>,
+}
+
+impl ActionResult {
+    /// Fields in output order, the CSV columns.
+    pub const FIELDS: &'static [&'static str] =
+        &["action", "status", "topic", "partition", "offset", "id", "target_topic", "error"];
+
+    pub fn new(action: Action, status: ActionStatus, record: &DlqRecord, target_topic: Option<&str>) -> Self {
+        Self {
+            action,
+            status,
+            topic: record.topic.clone(),
+            partition: record.partition,
+            offset: record.offset,
+            id: record.parse_error.is_none().then(|| record.message.id.clone()),
+            target_topic: target_topic.map(String::from),
+            error: None,
+        }
+    }
+
+    pub fn failed(action: Action, record: &DlqRecord, target_topic: Option<&str>, error: &anyhow::Error) -> Self {
+        Self {
+            error: Some(format!("{:#}", error)),
+            ..Self::new(action, ActionStatus::Failed, record, target_topic)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Archive,
+    Discard,
+    Republish,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionStatus {
+    /// Dry run: nothing was changed
+    Planned,
+    Cancelled,
+    Archived,
+    Discarded,
+    Republished,
+    Failed,
+}
+
+impl fmt::Display for ActionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            ActionStatus::Planned => "planned",
+            ActionStatus::Cancelled => "cancelled",
+            ActionStatus::Archived => "archived",
+            ActionStatus::Discarded => "discarded",
+            ActionStatus::Republished => "republished",
+            ActionStatus::Failed => "failed",
+        };
+        f.write_str(status)
+    }
+}
+
+/// Print records in a machine-readable format: a JSON array, one JSON object
+/// per line, CSV with a header row, or a YAML sequence. `fields` are the
+/// record's fields, which CSV prints as columns in that order whether or
+/// not any record has them.
+pub fn print_records<T: Serialize>(format: OutputFormat, fields: &[&str], records: &[T]) -> Result<(), anyhow::Error> {
+    let mut out = io::stdout().lock();
+    write_records(&mut out, format, fields, records)?;
+    Ok(())
+}
+
+fn write_records<T: Serialize>(
+    out: &mut impl Write,
+    format: OutputFormat,
+    fields: &[&str],
+    records: &[T],
+) -> Result<(), anyhow::Error> {
+    let values = records
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<Value>, _>>()?;
+
+    match format {
+        OutputFormat::Table => unreachable!("tables are printed by each command"),
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, &values)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Ndjson => {
+            for value in &values {
+                serde_json::to_writer(&mut *out, value)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => write_csv(out, fields, &values)?,
+        OutputFormat::Yaml => {
+            let mut text = String::new();
+            write_yaml(&Value::Array(values), 0, &mut text);
+            writeln!(out, "{}", text.trim_start())?;
+        }
+    }
+    Ok(())
+}
+
+/// One column per field; nested values are written as JSON.
+fn write_csv(out: &mut impl Write, columns: &[&str], values: &[Value]) -> io::Result<()> {
+    let header: Vec<String> = columns.iter().map(|column| csv_field(column)).collect();
+    writeln!(out, "{}", header.join(","))?;
+    for value in values {
+        let row: Vec<String> = columns
+            .iter()
+            .map(|column| match value.get(column) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(text)) => csv_field(text),
+                Some(other) => csv_field(&other.to_string()),
+            })
+            .collect();
+        writeln!(out, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Append `value` as YAML to `out`, which ends just after a `key:` or `-`.
+/// Scalars are written in their JSON form, which YAML reads the same way.
+fn write_yaml(value: &Value, indent: usize, out: &mut String) {
+    match value {
+        Value::Object(map) if !map.is_empty() => write_yaml_entries(map, indent, out, false),
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                out.push('\n');
+                out.push_str(&" ".repeat(indent));
+                out.push('-');
+                match item {
+                    Value::Object(map) if !map.is_empty() => {
+                        out.push(' ');
+                        write_yaml_entries(map, indent + 2, out, true);
+                    }
+                    _ => write_yaml(item, indent + 2, out),
+                }
+            }
+        }
+        Value::Object(_) => out.push_str(" {}"),
+        Value::Array(_) => out.push_str(" []"),
+        scalar => {
+            out.push(' ');
+            out.push_str(&scalar.to_string());
+        }
+    }
+}
+
+fn write_yaml_entries(map: &Map<String, Value>, indent: usize, out: &mut String, inline_first: bool) {
+    for (index, (key, value)) in map.iter().enumerate() {
+        if index > 0 || !inline_first {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent));
+        }
+        // Quote keys YAML would read as something other than a string
+        let plain = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+            && !["true", "false", "null", "yes", "no", "on", "off", "y", "n", "~"]
+                .contains(&key.to_ascii_lowercase().as_str());
+        if plain {
+            out.push_str(key);
+        } else {
+            out.push_str(&Value::String(key.clone()).to_string());
+        }
+        out.push(':');
+        write_yaml(value, indent + 2, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn written(format: OutputFormat, fields: &[&str], records: &[Value]) -> String {
+        let mut out = vec![];
+        write_records(&mut out, format, fields, records).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn read_csv(text: &str) -> (Vec<String>, Vec<Vec<String>>) {
+        let mut reader = csv::Reader::from_reader(text.as_bytes());
+        let header = reader.headers().unwrap().iter().map(String::from).collect();
+        let rows = reader
+            .records()
+            .map(|row| row.unwrap().iter().map(String::from).collect())
+            .collect();
+        (header, rows)
+    }
+
+    #[test]
+    fn csv_header_comes_from_the_fields() {
+        let (header, rows) = read_csv(&written(OutputFormat::Csv, ActionResult::FIELDS, &[]));
+        assert_eq!(header, ActionResult::FIELDS);
+        assert!(rows.is_empty());
+
+        let records = [json!({ "b": 1 }), json!({ "c": 2 })];
+        let (header, rows) = read_csv(&written(OutputFormat::Csv, &["a", "b"], &records));
+        assert_eq!(header, ["a", "b"]);
+        assert_eq!(rows, [["", "1"], ["", ""]]);
+    }
+
+    #[test]
+    fn csv_round_trips_awkward_values() {
+        let text = "comma, \"quotes\"\nand a newline\r\n";
+        let records = [json!({
+            "text": text,
+            "nested": { "list": [1, "two"], "empty": {} },
+            "missing": null,
+        })];
+        let (_, rows) = read_csv(&written(OutputFormat::Csv, &["text", "nested", "missing"], &records));
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0][0], text);
+        let nested: Value = serde_json::from_str(&rows[0][1]).unwrap();
+        assert_eq!(nested, records[0]["nested"]);
+        assert_eq!(rows[0][2], "");
+    }
+
+    #[test]
+    fn yaml_round_trips() {
+        let records = [
+            json!({
+                "true": "null",
+                "yes": false,
+                "null": null,
+                "~": "~",
+                "key with spaces": "line one\nline two",
+                "-dash": "# not a comment",
+                "123": 1.5,
+                "nested": { "list": [1, [2, 3], { "a": "b" }], "empty_list": [], "empty_map": {} },
+            }),
+            json!({}),
+            json!("plain"),
+        ];
+        let read: Value = serde_yaml::from_str(&written(OutputFormat::Yaml, &[], &records)).unwrap();
+        assert_eq!(read, json!(records));
+
+        let read: Value = serde_yaml::from_str(&written(OutputFormat::Yaml, &[], &[])).unwrap();
+        assert_eq!(read, json!([]));
+    }
+
+    #[test]
+    fn action_result_fields_match_its_serialized_keys() {
+        let result = ActionResult {
+            action: Action::Archive,
+            status: ActionStatus::Failed,
+            topic: "dlq".to_string(),
+            partition: 0,
+            offset: 1,
+            id: None,
+            target_topic: None,
+            error: None,
+        };
+        let value = serde_json::to_value(result).unwrap();
+        let mut keys: Vec<&str> = value.as_object().unwrap().keys().map(String::as_str).collect();
+        let mut fields = ActionResult::FIELDS.to_vec();
+        keys.sort();
+        fields.sort();
+        assert_eq!(keys, fields);
+    }
+}
@@ -1,185 +1,224 @@
 use std::{
+    collections::HashSet,
     io::{self, Write},
     path::Path,
-    time::Duration,
 };
 
 use anyhow::{anyhow, Context};
 use colored::Colorize;
-use log::{info, warn};
-use rdkafka::{
-    config::RDKafkaLogLevel,
-    consumer::{CommitMode, Consumer},
-    message::{BorrowedMessage, Headers, OwnedHeaders},
-    producer::{FutureProducer, FutureRecord},
-    ClientConfig, Message,
-};
-use serde_json::from_str;
+use rdkafka::producer::FutureProducer;
 use similar::{ChangeTag, TextDiff};
 
 use crate::{
-    cli::DlqMessage,
+    cli::{
+        print_records, Action, ActionResult, ActionStatus, BulkSelection, DlqMessage, MessageSelector,
+        OutputFormat,
+    },
+    envelope::{self, cloudevents, EnvelopeFormat, OutgoingRecord},
     kafka::{
-        CustomContext, LoggingConsumer, AUTO_OFFSET_RESET, BOOTSTRAP_SERVERS, ENABLE_AUTO_COMMIT,
-        ENABLE_PARTITION_EOF, GROUP_ID, MESSAGE_HEADER_CORRELATION_ID, MESSAGE_HEADER_MESSAGE_ID,
-        MESSAGE_HEADER_REPUBLISHED_AT, MESSAGE_TIMEOUT_MS, SESSION_TIMEOUT_MS, TIMEOUT_MS,
+        producer_config, send_record, DlqRecord, ParseFailurePolicy, RecordHeader, ScanMode,
+        MESSAGE_HEADER_CORRELATION_ID, MESSAGE_HEADER_MESSAGE_ID, MESSAGE_HEADER_REPUBLISHED_AT,
     },
+    schema::{wire_schema_id, AvroCodec, PayloadEncoding, ProtobufCodec},
+    settings::Settings,
 };
 
 pub async fn republish_message(
-    brokers: &str,
-    group_id: &str,
+    settings: &Settings,
     dlq_topic: &str,
-    message_id: &str,
+    selector: &MessageSelector,
     payload_file: Option<&Path>,
     dry_run: bool,
+    target_topic: Option<&str>,
+    output: OutputFormat,
 ) -> Result<(), anyhow::Error> {
     // find message in dlq
-    let context = CustomContext;
-    let mut config = ClientConfig::new();
-
-    config
-        .set(GROUP_ID, group_id)
-        .set(BOOTSTRAP_SERVERS, brokers)
-        .set(ENABLE_PARTITION_EOF, "true") // Enable EOF detection
-        .set(AUTO_OFFSET_RESET, "earliest") // Start from beginning
-        .set(ENABLE_AUTO_COMMIT, "false") // Don't auto-commit
-        .set(SESSION_TIMEOUT_MS, TIMEOUT_MS)
-        .set_log_level(RDKafkaLogLevel::Debug);
-
-    let consumer: LoggingConsumer = config
-        .create_with_context(context)
-        .context("Consumer creation failed")?;
-
-    let topics = &[dlq_topic];
-    consumer
-        .subscribe(topics)
-        .map_err(|e| anyhow!("Failed to subscribe to topic: {}", e))?;
-
-    let mut dlq_message: Option<DlqMessage> = None;
-    let mut borrowed_message: Option<BorrowedMessage> = None;
-    loop {
-        match consumer.recv().await {
-            Err(e) => {
-                if let rdkafka::error::KafkaError::PartitionEOF(_) = e {
-                    info!("Reached end of partition (EOF)");
-                    break;
-                } else {
-                    warn!("Kafka error: {}", e);
-                    break; // Exit on other errors
-                }
-            }
-            Ok(m) => {
-                let payload = match m.payload_view::<str>() {
-                    None => {
-                        warn!("No payload found in message, skipping");
-                        continue;
-                    }
-                    Some(Ok(s)) => s,
-                    Some(Err(e)) => {
-                        warn!("Error while deserializing message payload: {:?}", e);
-                        continue;
-                    }
-                };
-
-                let json: serde_json::Value = match from_str(payload) {
-                    Ok(j) => j,
-                    Err(e) => {
-                        warn!("Failed to parse message as JSON: {}, skipping", e);
-                        continue;
-                    }
-                };
-
-                let item = DlqMessage::parse(json);
-                if item.id.eq(message_id) || item.correlation_id.eq(message_id) {
-                    dlq_message = Some(item);
-                    borrowed_message = Some(m);
-                    break;
-                }
-            }
-        };
+    let mut scanner = selector
+        .scanner(settings, dlq_topic, ScanMode::Group)?
+        .on_parse_failure(ParseFailurePolicy::Keep);
+
+    let selection = BulkSelection::scan(&mut scanner, selector, None).await?;
+    let record = selection.single(&scanner)?;
+    if let Some(error) = &record.parse_error {
+        return Err(anyhow!(
+            "Cannot republish partition {} offset {}: {}",
+            record.partition,
+            record.offset,
+            error
+        ));
     }
 
-    let dlq_message = dlq_message.with_context(|| "Message not found")?;
-    let original_topic = dlq_message.original_topic.as_str();
-    let mut message_json =
-        serde_json::to_value(&dlq_message).context("Failed to convert DLQ message to JSON")?;
-
-    let mut headers = OwnedHeaders::new();
-    headers = headers
-        .insert(rdkafka::message::Header {
-            key: MESSAGE_HEADER_MESSAGE_ID,
-            value: Some(dlq_message.id.as_bytes()),
-        })
-        .insert(rdkafka::message::Header {
-            key: MESSAGE_HEADER_REPUBLISHED_AT,
-            value: Some(chrono::Utc::now().to_rfc3339().as_bytes()),
-        });
-
-    if !dlq_message.correlation_id.is_empty() {
-        headers = headers.insert(rdkafka::message::Header {
-            key: MESSAGE_HEADER_CORRELATION_ID,
-            value: Some(dlq_message.correlation_id.as_bytes()),
-        });
+    let dlq_message = &record.message;
+    let original_topic = match target_topic {
+        Some(topic) => topic,
+        None if dlq_message.original_topic == "-" => {
+            return Err(anyhow!(
+                "Original topic of message {} is unknown; pass --target-topic",
+                dlq_message.id
+            ));
+        }
+        None => dlq_message.original_topic.as_str(),
+    };
+    let mapping = settings.envelope_mapping(dlq_topic);
+
+    // Records without a JSON body may be replaced by plain text
+    let allow_text = record.envelope.is_null();
+    let new_payload = payload_file
+        .map(|path| read_payload_from_file(path, allow_text))
+        .transpose()?;
+    let mut outgoing = envelope::redrive(record, new_payload.as_ref(), &mapping)?;
+    // An edited Avro or protobuf payload is encoded the way it was (or
+    // should have been) read, never sent as JSON
+    if let Some(new_payload) = &new_payload {
+        if let Some(encoding) = payload_encoding(record, settings, dlq_topic)? {
+            outgoing.payload = match encoding {
+                PayloadEncoding::Avro { schema_id } => {
+                    AvroCodec::from_settings(settings)
+                        .with_context(|| {
+                            format!("The payload is Avro (schema id {}), but no schema source is configured", schema_id)
+                        })?
+                        .encode(schema_id, new_payload)
+                        .await?
+                }
+                PayloadEncoding::Protobuf => ProtobufCodec::from_settings(settings, dlq_topic)?
+                    .context("No protobuf message type configured to encode the payload")?
+                    .encode(new_payload)?,
+            };
+        }
     }
-    
-    // handle payload replacement if file is provided
-    let mut new_payload: Option<String> = None;
-    if let Some(file_path) = payload_file {
-        let payload = read_payload_from_file(file_path)?;
-        let payload = serde_json::to_string_pretty(&payload)
-            .context("Failed to convert new payload to JSON")?;
-
-        new_payload = Some(payload.clone());
-
-        display_diff_and_plan(
-            &dlq_message,
-            &dlq_message.payload.to_string(),
-            &payload,
-            dlq_topic,
-            file_path,
-            dry_run,
-            &headers,
-        )?;
-    } else {
-        display_republish_info(&dlq_message, dlq_topic, dry_run, &headers)?;
+
+    add_republish_headers(&mut outgoing, dlq_message, &chrono::Utc::now().to_rfc3339());
+
+    let result = |status| ActionResult::new(Action::Republish, status, record, Some(original_topic));
+
+    // Machine formats print the plan or the result instead
+    if output.is_table() {
+        if let (Some(file_path), Some(new_payload)) = (payload_file, &new_payload) {
+            let payload = match new_payload {
+                serde_json::Value::String(text) => text.clone(),
+                json => serde_json::to_string_pretty(json)
+                    .context("Failed to convert new payload to JSON")?,
+            };
+
+            display_diff_and_plan(
+                dlq_message,
+                &payload,
+                dlq_topic,
+                original_topic,
+                file_path,
+                dry_run,
+                &outgoing.headers,
+            )?;
+        } else {
+            display_republish_info(dlq_message, dlq_topic, original_topic, dry_run, &outgoing.headers)?;
+        }
     }
 
     if dry_run {
-        print_dry_run_info(dlq_topic, original_topic);
+        if output.is_table() {
+            print_dry_run_info(dlq_topic, original_topic);
+        } else {
+            print_records(output, ActionResult::FIELDS, &[result(ActionStatus::Planned)])?;
+        }
         return Ok(());
     }
 
     if !confirm_action()? {
-        println!("Operation cancelled.");
+        if output.is_table() {
+            println!("Operation cancelled.");
+        } else {
+            print_records(output, ActionResult::FIELDS, &[result(ActionStatus::Cancelled)])?;
+        }
         return Ok(());
     }
 
     // publish to original topic
-    println!("\nPublishing to {}...", original_topic);
-    message_json["payload"] =
-        serde_json::to_value(new_payload).context("Failed to convert new payload to JSON")?;
-
-    publish_message(brokers, original_topic, &message_json, message_id, headers).await?;
-    println!("✅ Message published successfully");
-
-    // commit DLQ offset
-    if let Some(borrowed_message) = borrowed_message {
-        consumer
-            .commit_message(&borrowed_message, CommitMode::Sync)
-            .context("Failed to commit DLQ offset")?;
-        println!("✅ DLQ message committed (removed from {})", dlq_topic);
+    if output.is_table() {
+        println!("\nPublishing to {}...", original_topic);
+    }
+
+    let producer: FutureProducer = producer_config(settings)
+        .create()
+        .context("Producer creation failed")?;
+    send_record(&producer, original_topic, &outgoing).await?;
+
+    // commit DLQ offset, unless that would mark other records handled
+    selection.commit(&scanner, &HashSet::from([(record.partition, record.offset)]))?;
+    let uncommittable = selection.uncommittable(record);
+    if let Some(reason) = &uncommittable {
+        eprintln!(
+            "{} Partition {} offset {} was republished but not committed: {}",
+            "WARNING:".yellow().bold(),
+            record.partition,
+            record.offset,
+            reason
+        );
     }
 
-    println!("\nDone!");
+    if output.is_table() {
+        println!("✅ Message published successfully");
+        if uncommittable.is_none() {
+            println!("✅ DLQ message committed (removed from {})", dlq_topic);
+        }
+        println!("\nDone!");
+    } else {
+        print_records(output, ActionResult::FIELDS, &[result(ActionStatus::Republished)])?;
+    }
     Ok(())
 }
 
+/// How a replacement payload for `record` must be encoded. Records whose
+/// payload failed to decode still need it: a wire-format payload keeps its
+/// schema id, and a topic with a protobuf type only takes that type.
+fn payload_encoding(
+    record: &DlqRecord,
+    settings: &Settings,
+    dlq_topic: &str,
+) -> Result<Option<PayloadEncoding>, anyhow::Error> {
+    if record.payload_encoding.is_some() {
+        return Ok(record.payload_encoding);
+    }
+    // JSON envelopes and structured CloudEvents carry the payload in the body
+    let structured = record.format == EnvelopeFormat::CloudEvents && !cloudevents::is_binary(record);
+    if record.format == EnvelopeFormat::Json || structured {
+        return Ok(None);
+    }
+
+    if let Some(schema_id) = record.payload.as_deref().and_then(wire_schema_id) {
+        return Ok(Some(PayloadEncoding::Avro { schema_id }));
+    }
+    if ProtobufCodec::from_settings(settings, dlq_topic)?.is_some() {
+        return Ok(Some(PayloadEncoding::Protobuf));
+    }
+    Ok(None)
+}
+
+/// Tag a republished record with the message and correlation ids and the
+/// time it was republished.
+pub fn add_republish_headers(outgoing: &mut OutgoingRecord, message: &DlqMessage, republished_at: &str) {
+    outgoing.headers.push(header(MESSAGE_HEADER_MESSAGE_ID, &message.id));
+    outgoing.headers.push(header(MESSAGE_HEADER_REPUBLISHED_AT, republished_at));
+    if !message.correlation_id.is_empty() && message.correlation_id != "-" {
+        outgoing.headers.push(header(MESSAGE_HEADER_CORRELATION_ID, &message.correlation_id));
+    }
+}
+
+fn header(key: &str, value: &str) -> RecordHeader {
+    RecordHeader {
+        key: key.to_string(),
+        value: Some(value.as_bytes().to_vec()),
+    }
+}
+
 /// Read and parse payload from JSON file
-fn read_payload_from_file(path: &Path) -> Result<serde_json::Value, anyhow::Error> {
+fn read_payload_from_file(path: &Path, allow_text: bool) -> Result<serde_json::Value, anyhow::Error> {
     let content = std::fs::read_to_string(path)
         .context(format!("Failed to read file: {}", path.display()))?;
 
+    if allow_text && serde_json::from_str::<serde_json::Value>(&content).is_err() {
+        return Ok(serde_json::Value::String(content));
+    }
+
     let payload: serde_json::Value = serde_json::from_str(&content)
         .context(format!("Invalid JSON in file: {}", path.display()))?;
 
@@ -189,15 +228,13 @@ fn read_payload_from_file(path: &Path) -> Result<serde_json::Value, anyhow::Erro
 /// Display diff when payload is being changed
 fn display_diff_and_plan(
     dlq_message: &DlqMessage,
-    old_payload: &str,
     new_payload: &str,
     dlq_topic: &str,
+    original_topic: &str,
     payload_file: &Path,
     dry_run: bool,
-    headers: &OwnedHeaders,
+    headers: &[RecordHeader],
 ) -> Result<(), anyhow::Error> {
-    let original_topic = dlq_message.original_topic.as_str();
-
     println!("\n{}", "=".repeat(65));
     println!("Found message in DLQ: {}", dlq_topic.cyan());
     if dry_run {
@@ -211,11 +248,17 @@ fn display_diff_and_plan(
     println!("  Failure Reason: {}", dlq_message.reason);
     println!("  Retry Count:    {}", dlq_message.retries);
     println!("  Original Topic: {}", dlq_message.original_topic.green());
+    if let (Some(partition), Some(offset)) = (dlq_message.original_partition, dlq_message.original_offset) {
+        println!("  Original Pos:   partition {} offset {}", partition, offset);
+    }
+    if let Some(connector) = &dlq_message.connector {
+        println!("  Connector:      {} (task {})", connector, dlq_message.task.as_deref().unwrap_or("-"));
+    }
     println!("  Moved to DLQ:   {}", dlq_message.moved_at);
 
     println!("\n{}", "MESSAGE HEADERS (will be republished as-is):".bold().yellow());
-    for header in headers.iter() {
-        println!("  {}: {}", header.key, String::from_utf8_lossy(header.value.unwrap_or_default()));
+    for header in headers {
+        println!("  {}: {}", header.key, String::from_utf8_lossy(header.value.as_deref().unwrap_or_default()));
     }
 
     println!("\n{}", "=".repeat(65));
@@ -223,7 +266,7 @@ fn display_diff_and_plan(
     println!("{}", "=".repeat(65));
 
     println!("\n{}", "BEFORE (Current in DLQ):".yellow());
-    println!("{}", old_payload);
+    println!("{}", dlq_message.payload);
 
     println!(
         "\n{}",
@@ -232,7 +275,7 @@ fn display_diff_and_plan(
     println!("{}", new_payload);
 
     println!("\n{}", "DIFF:".cyan());
-    print_diff(old_payload, new_payload);
+    print_diff(&dlq_message.payload, new_payload);
 
     println!("\n{}", "=".repeat(65));
     println!("{}", "PLANNED ACTION:".bold());
@@ -270,11 +313,10 @@ fn display_diff_and_plan(
 fn display_republish_info(
     dlq_message: &DlqMessage,
     dlq_topic: &str,
+    original_topic: &str,
     dry_run: bool,
-    headers: &OwnedHeaders,
+    headers: &[RecordHeader],
 ) -> Result<(), anyhow::Error> {
-    let original_topic = dlq_message.original_topic.as_str();
-
     println!("\n{}", "=".repeat(65));
     println!("Found message in DLQ: {}", dlq_topic.cyan());
     if dry_run {
@@ -288,11 +330,17 @@ fn display_republish_info(
     println!("  Failure Reason: {}", dlq_message.reason);
     println!("  Retry Count:    {}", dlq_message.retries);
     println!("  Original Topic: {}", dlq_message.original_topic.green());
+    if let (Some(partition), Some(offset)) = (dlq_message.original_partition, dlq_message.original_offset) {
+        println!("  Original Pos:   partition {} offset {}", partition, offset);
+    }
+    if let Some(connector) = &dlq_message.connector {
+        println!("  Connector:      {} (task {})", connector, dlq_message.task.as_deref().unwrap_or("-"));
+    }
     println!("  Moved to DLQ:   {}", dlq_message.moved_at);
 
     println!("\n{}", "MESSAGE HEADERS (will be republished as-is):".yellow());
-    for header in headers.iter() {
-        println!("  {}: {}", header.key, String::from_utf8_lossy(header.value.unwrap_or_default()));
+    for header in headers {
+        println!("  {}: {}", header.key, String::from_utf8_lossy(header.value.as_deref().unwrap_or_default()));
     }
 
     println!("\n{}", "PAYLOAD (will be republished as-is):".yellow());
@@ -362,40 +410,11 @@ fn print_dry_run_info(dlq_topic: &str, original_topic: &str) {
     );
 }
 
-/// Publish message to Kafka topic
-async fn publish_message(
-    brokers: &str,
-    topic: &str,
-    message: &serde_json::Value,
-    key: &str,
-    headers: OwnedHeaders,
-) -> Result<(), anyhow::Error> {
-    let producer: FutureProducer = ClientConfig::new()
-        .set(BOOTSTRAP_SERVERS, brokers)
-        .set(MESSAGE_TIMEOUT_MS, TIMEOUT_MS)
-        .create()
-        .context("Producer creation failed")?;
-
-    let payload = serde_json::to_vec(message).context("Failed to serialize message")?;
-
-    producer
-        .send(
-            FutureRecord::to(topic)
-                .payload(&payload)
-                .key(key)
-                .headers(headers),
-            Duration::from_secs(0),
-        )
-        .await
-        .map_err(|(err, _)| anyhow!("Failed to publish: {}", err))?;
-
-    Ok(())
-}
-
-/// Ask user for confirmation
-fn confirm_action() -> Result<bool, anyhow::Error> {
-    print!("\n{} ", "Proceed with this change? [y/N]:".yellow().bold());
-    io::stdout().flush()?;
+/// Ask user for confirmation. The prompt goes to stderr so that it does not
+/// mix with machine-readable output.
+pub fn confirm_action() -> Result<bool, anyhow::Error> {
+    eprint!("\n{} ", "Proceed with this change? [y/N]:".yellow().bold());
+    io::stderr().flush()?;
 
     let mut input = String::new();
     io::stdin().read_line(&mut input)?;
@@ -0,0 +1,161 @@
+use std::{collections::HashSet, path::Path};
+
+use anyhow::{anyhow, bail, Context};
+use rdkafka::producer::FutureProducer;
+
+use crate::{
+    cli::{
+        add_republish_headers, confirm_action, print_records, print_results, produce_all, read_ids,
+        Action, ActionResult, ActionStatus, BulkArgs, BulkSelection, EnvelopeMapping, MessageSelector,
+        OutputFormat, Progress, RepublishArgs,
+    },
+    envelope::{self, OutgoingRecord},
+    kafka::{producer_config, DlqRecord, ParseFailurePolicy, ScanMode},
+    settings::Settings,
+};
+
+pub async fn republish_messages(
+    settings: &Settings,
+    topic: &str,
+    selector: &MessageSelector,
+    bulk: &BulkArgs,
+    republish: &RepublishArgs,
+    output: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    if bulk.ids.as_deref() == Some(Path::new("-")) && !bulk.dry_run && !republish.yes {
+        bail!("--ids - reads stdin, which the confirmation needs too; pass --yes");
+    }
+    let ids = bulk.ids.as_deref().map(read_ids).transpose()?;
+    match &ids {
+        None if selector.is_empty() => {
+            bail!("Give --ids or filter criteria (--filter, --where, --key, --header, --since, --until)")
+        }
+        Some(ids) if ids.is_empty() => bail!("The ID list is empty"),
+        _ => {}
+    }
+
+    let mut scanner = selector
+        .scanner(settings, topic, ScanMode::Group)?
+        .on_parse_failure(ParseFailurePolicy::Keep);
+    let selection = BulkSelection::scan(&mut scanner, selector, ids.as_deref()).await?;
+    let mapping = scanner.mapping().clone();
+    let total = selection.records.len();
+
+    // Prepared up front so that the plan can count what cannot be republished
+    let republished_at = chrono::Utc::now().to_rfc3339();
+    let jobs: Vec<Result<(String, OutgoingRecord), anyhow::Error>> = selection
+        .records
+        .iter()
+        .map(|record| prepare(record, republish.target_topic.as_deref(), &mapping, &republished_at))
+        .collect();
+    let targets: Vec<Option<String>> = jobs
+        .iter()
+        .map(|job| job.as_ref().ok().map(|(target, _)| target.clone()))
+        .collect();
+    let result = |index: usize, status| {
+        ActionResult::new(Action::Republish, status, &selection.records[index], targets[index].as_deref())
+    };
+    let failed_early = |index: usize| match &jobs[index] {
+        Err(e) => Some(ActionResult::failed(Action::Republish, &selection.records[index], None, e)),
+        Ok(_) => None,
+    };
+
+    let destination = republish.target_topic.as_deref().unwrap_or("their original topics");
+    let totals = format!("{} message(s) to republish from {} to {}", total, topic, destination);
+    let unusable = jobs.iter().filter(|job| job.is_err()).count();
+    if output.is_table() {
+        println!("{}", totals);
+        selection.print_plan();
+        if unusable > 0 {
+            println!("{} of them cannot be republished and will be reported as failed", unusable);
+        }
+        if let Some(rate) = republish.limits.rate {
+            println!("At most {} message(s) per second: about {:.0}s", rate, total as f64 / rate);
+        }
+    }
+
+    if bulk.dry_run || total == 0 {
+        if output.is_table() {
+            println!("{}: {}", topic, scanner.summary());
+        } else {
+            let planned: Vec<ActionResult> = (0..total)
+                .map(|index| failed_early(index).unwrap_or_else(|| result(index, ActionStatus::Planned)))
+                .collect();
+            print_records(output, ActionResult::FIELDS, &planned)?;
+        }
+        return Ok(());
+    }
+
+    if !republish.yes {
+        if !output.is_table() {
+            eprintln!("{}", totals);
+        }
+        if !confirm_action()? {
+            if output.is_table() {
+                println!("Operation cancelled.");
+            } else {
+                let cancelled: Vec<ActionResult> =
+                    (0..total).map(|index| result(index, ActionStatus::Cancelled)).collect();
+                print_records(output, ActionResult::FIELDS, &cancelled)?;
+            }
+            return Ok(());
+        }
+    }
+
+    let producer: FutureProducer = producer_config(settings)
+        .create()
+        .context("Producer creation failed")?;
+
+    let mut results: Vec<ActionResult> = Vec::with_capacity(total);
+    let mut handled: HashSet<(i32, i64)> = HashSet::new();
+    let mut progress = Progress::new("Republished", total);
+    produce_all(&producer, jobs, republish.limits, |index, delivered| {
+        let record = &selection.records[index];
+        progress.record(delivered.is_ok());
+        results.push(match delivered {
+            Ok(()) => {
+                handled.insert((record.partition, record.offset));
+                result(index, ActionStatus::Republished)
+            }
+            Err(e) => ActionResult::failed(Action::Republish, record, targets[index].as_deref(), &e),
+        });
+    })
+    .await;
+    progress.finish();
+
+    selection.commit(&scanner, &handled)?;
+
+    if output.is_table() {
+        print_results(&results);
+        println!("Republished {} of {} message(s)", handled.len(), total);
+    } else {
+        print_records(output, ActionResult::FIELDS, &results)?;
+    }
+
+    let failed = total - handled.len();
+    if failed > 0 {
+        return Err(anyhow!("{} message(s) could not be republished", failed));
+    }
+    Ok(())
+}
+
+/// The record to send back for `record`, and the topic to send it to.
+fn prepare(
+    record: &DlqRecord,
+    target_topic: Option<&str>,
+    mapping: &EnvelopeMapping,
+    republished_at: &str,
+) -> Result<(String, OutgoingRecord), anyhow::Error> {
+    if let Some(error) = &record.parse_error {
+        bail!("Cannot republish a record that could not be decoded: {}", error);
+    }
+    let topic = match target_topic {
+        Some(topic) => topic,
+        None if record.message.original_topic == "-" => bail!("Original topic is unknown; pass --target-topic"),
+        None => record.message.original_topic.as_str(),
+    };
+
+    let mut outgoing = envelope::redrive(record, None, mapping)?;
+    add_republish_headers(&mut outgoing, &record.message, republished_at);
+    Ok((topic.to_string(), outgoing))
+}
@@ -0,0 +1,288 @@
+use std::{fmt, str::FromStr};
+
+use serde_json::Value;
+
+use crate::{
+    cli::EnvelopeMapping,
+    envelope,
+    filter::Filter,
+    kafka::{Cursor, DlqRecord, DlqScanner, ParseFailurePolicy, ScanMode, TimeRange},
+    settings::Settings,
+};
+
+/// Which DLQ records a command works on. Every criterion that is set must
+/// match; several records may match.
+#[derive(Debug, Clone, Default)]
+pub struct MessageSelector {
+    /// Matches the message id or correlation id.
+    pub id: Option<String>,
+    /// Kafka coordinates. The only criterion that also addresses a single
+    /// record that cannot be decoded, and the only one that seeks directly.
+    pub position: Option<(i32, i64)>,
+    /// Kafka record key, compared as text.
+    pub key: Option<String>,
+    /// Header name and value, compared as text.
+    pub headers: Vec<(String, String)>,
+    pub conditions: Vec<JsonCondition>,
+    pub filter: Option<Filter>,
+    /// Record timestamps to scan; the scanner seeks to them.
+    pub range: TimeRange,
+    /// Where an earlier read-only scan stopped.
+    pub cursor: Option<Cursor>,
+}
+
+impl MessageSelector {
+    /// Whether `record` is selected. Criteria that look at the decoded
+    /// message never match records that could not be decoded.
+    pub fn matches(&self, record: &DlqRecord, mapping: &EnvelopeMapping) -> bool {
+        if let Some((partition, offset)) = self.position {
+            if record.partition != partition || record.offset != offset {
+                return false;
+            }
+        }
+        if !self.range.contains(record.timestamp) {
+            return false;
+        }
+        if let Some(key) = &self.key {
+            if record.key_text().as_deref() != Some(key.as_str()) {
+                return false;
+            }
+        }
+        for (name, value) in &self.headers {
+            let header = record.header(name).map(String::from_utf8_lossy);
+            if header.as_deref() != Some(value.as_str()) {
+                return false;
+            }
+        }
+
+        if !self.inspects_message() {
+            return true;
+        }
+        if record.parse_error.is_some() {
+            return false;
+        }
+        if let Some(id) = &self.id {
+            if record.message.id != *id && record.message.correlation_id != *id {
+                return false;
+            }
+        }
+        if !self.conditions.is_empty() || self.filter.is_some() {
+            let document = envelope::document(record, mapping);
+            return self.conditions.iter().all(|condition| condition.matches(&document))
+                && self.filter.as_ref().is_none_or(|filter| filter.matches(&document));
+        }
+        true
+    }
+
+    /// Whether no criterion is set, so that every record is selected.
+    pub fn is_empty(&self) -> bool {
+        self.id.is_none()
+            && self.position.is_none()
+            && self.key.is_none()
+            && self.headers.is_empty()
+            && self.conditions.is_empty()
+            && self.filter.is_none()
+            && self.range.is_unbounded()
+    }
+
+    /// Whether any criterion needs the decoded message.
+    fn inspects_message(&self) -> bool {
+        self.id.is_some() || !self.conditions.is_empty() || self.filter.is_some()
+    }
+
+    /// A scanner for the selected records: a direct seek for coordinates,
+    /// otherwise a scan of the whole topic.
+    pub fn scanner(
+        &self,
+        settings: &Settings,
+        topic: &str,
+        mode: ScanMode,
+    ) -> Result<DlqScanner, anyhow::Error> {
+        let scanner = match self.position {
+            Some((partition, offset)) => DlqScanner::at(settings, topic, mode, partition, offset)?,
+            None => match &self.cursor {
+                Some(cursor) => DlqScanner::resume(settings, topic, mode, self.range, cursor)?,
+                None => DlqScanner::in_range(settings, topic, mode, self.range)?,
+            },
+        };
+        Ok(scanner.on_parse_failure(self.parse_failure_policy()))
+    }
+
+    /// Undecodable records are kept when they can be selected, i.e. when
+    /// only Kafka-level criteria are given.
+    pub fn parse_failure_policy(&self) -> ParseFailurePolicy {
+        if !self.inspects_message() {
+            ParseFailurePolicy::Keep
+        } else {
+            ParseFailurePolicy::Skip
+        }
+    }
+
+    /// Every selected record in the topic.
+    pub async fn find_all(&self, scanner: &mut DlqScanner) -> Result<Vec<DlqRecord>, anyhow::Error> {
+        let mapping = scanner.mapping().clone();
+        let mut records = vec![];
+        while let Some(record) = scanner.next().await? {
+            if self.matches(&record, &mapping) {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// A `--where` condition on the record document (see `envelope::document`):
+/// a path, `==` or `!=`, and a JSON value, e.g. `$.payload.userId == "user-123"`.
+#[derive(Debug, Clone)]
+pub struct JsonCondition {
+    text: String,
+    pointer: String,
+    equal: bool,
+    value: Value,
+}
+
+impl JsonCondition {
+    pub fn matches(&self, document: &Value) -> bool {
+        let actual = document.pointer(&self.pointer).unwrap_or(&Value::Null);
+        (*actual == self.value) == self.equal
+    }
+}
+
+impl fmt::Display for JsonCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl FromStr for JsonCondition {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let Some((i, equal)) = find_operator(text) else {
+            return Err(format!("expected `PATH == VALUE` or `PATH != VALUE`, got `{}`", text));
+        };
+        let (path, value) = (&text[..i], &text[i + 2..]);
+
+        let value = value.trim();
+        let value: Value = serde_json::from_str(value).map_err(|_| {
+            format!("`{}` is not a JSON value (strings need double quotes)", value)
+        })?;
+
+        Ok(Self {
+            text: text.to_string(),
+            pointer: path_to_pointer(path.trim())?,
+            equal,
+            value,
+        })
+    }
+}
+
+/// Position of the first `==` or `!=` outside brackets, so that keys such as
+/// `["a==b"]` can contain them, and whether it is `==`.
+fn find_operator(text: &str) -> Option<(usize, bool)> {
+    let (mut depth, mut quoted) = (0usize, false);
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' if depth > 0 => quoted = !quoted,
+            '[' if !quoted => depth += 1,
+            ']' if !quoted => depth = depth.saturating_sub(1),
+            '=' | '!' if depth == 0 && text[i + 1..].starts_with('=') => return Some((i, c == '=')),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Convert `$.a.b[0]["c d"]` into the JSON Pointer `/a/b/0/c d`.
+fn path_to_pointer(path: &str) -> Result<String, String> {
+    let invalid = || format!("invalid path `{}` (expected e.g. `$.payload.userId`)", path);
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+    let mut pointer = String::new();
+
+    while !rest.is_empty() {
+        let segment = if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            rest = &after[end..];
+            after[..end].to_string()
+        } else if let Some(after) = rest.strip_prefix("[\"") {
+            let end = after.find("\"]").ok_or_else(invalid)?;
+            rest = &after[end + 2..];
+            after[..end].to_string()
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            let index = &after[..end];
+            if index.parse::<usize>().is_err() {
+                return Err(invalid());
+            }
+            rest = &after[end + 1..];
+            index.to_string()
+        } else {
+            return Err(invalid());
+        };
+
+        if segment.is_empty() {
+            return Err(invalid());
+        }
+        pointer.push('/');
+        pointer.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+    }
+    Ok(pointer)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn condition(text: &str) -> JsonCondition {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn splits_at_the_first_operator_outside_brackets() {
+        let c = condition(r#"$.reason == "a != b""#);
+        assert_eq!((c.pointer.as_str(), c.equal, &c.value), ("/reason", true, &json!("a != b")));
+        let c = condition("$.retries!=3");
+        assert_eq!((c.pointer.as_str(), c.equal, &c.value), ("/retries", false, &json!(3)));
+        let c = condition(r#"$["a==b"] == 1"#);
+        assert_eq!((c.pointer.as_str(), c.equal), ("/a==b", true));
+        let c = condition(r#"$["x]!=y"] != null"#);
+        assert_eq!((c.pointer.as_str(), c.equal, &c.value), ("/x]!=y", false, &Value::Null));
+    }
+
+    #[test]
+    fn rejects_malformed_conditions() {
+        for text in ["$.reason", "$.reason = 1", r#"$["a==b"]"#] {
+            assert!(text.parse::<JsonCondition>().unwrap_err().starts_with("expected `PATH"), "{}", text);
+        }
+        let error = "$.reason == oops".parse::<JsonCondition>().unwrap_err();
+        assert_eq!(error, "`oops` is not a JSON value (strings need double quotes)");
+        assert!("reason == 1".parse::<JsonCondition>().unwrap_err().starts_with("invalid path"));
+    }
+
+    #[test]
+    fn converts_paths_to_pointers() {
+        assert_eq!(path_to_pointer("$"), Ok(String::new()));
+        assert_eq!(path_to_pointer("$.payload.userId"), Ok("/payload/userId".to_string()));
+        assert_eq!(path_to_pointer("$.items[0].id"), Ok("/items/0/id".to_string()));
+        assert_eq!(path_to_pointer(r#"$["c d"][12]"#), Ok("/c d/12".to_string()));
+        assert_eq!(path_to_pointer(r#"$["a.b"].c"#), Ok("/a.b/c".to_string()));
+        // `~` and `/` are escaped as JSON Pointer requires
+        assert_eq!(path_to_pointer(r#"$["a/b"]["~c"]"#), Ok("/a~1b/~0c".to_string()));
+
+        for path in ["payload", "$.", "$..a", "$.a[", "$[x]", "$[-1]", r#"$["a"#, "$[]", r#"$[""]"#, "$a"] {
+            assert!(path_to_pointer(path).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn matches_through_escaped_keys() {
+        let document = json!({ "headers": { "a/b": "1", "~c": 2 }, "items": [{ "id": "x" }] });
+        assert!(condition(r#"$.headers["a/b"] == "1""#).matches(&document));
+        assert!(condition(r#"$.headers["~c"] == 2"#).matches(&document));
+        assert!(condition(r#"$.items[0].id == "x""#).matches(&document));
+        assert!(condition("$.missing == null").matches(&document));
+        assert!(!condition("$.missing != null").matches(&document));
+    }
+}
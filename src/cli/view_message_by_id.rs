@@ -1,87 +1,76 @@
-use anyhow::{anyhow, Context};
-use log::{info, warn};
-use rdkafka::{config::RDKafkaLogLevel, consumer::Consumer, ClientConfig, Message};
-use serde_json::from_str;
+use anyhow::anyhow;
 use tabled::{settings::Rotate, Table};
 
-use crate::{cli::DlqMessage, kafka::{CustomContext, LoggingConsumer, AUTO_OFFSET_RESET, BOOTSTRAP_SERVERS, ENABLE_AUTO_COMMIT, ENABLE_PARTITION_EOF, GROUP_ID, SESSION_TIMEOUT_MS, TIMEOUT_MS}};
+use crate::{
+    cli::{print_records, MessageSelector, OutputFormat, UnparseableRecord},
+    envelope,
+    kafka::{DlqRecord, ScanMode},
+    settings::Settings,
+};
 
 pub async fn view_message_by_id(
-    brokers: &str,
-    group_id: &str,
+    settings: &Settings,
     topic: &str,
-    id: &str,
+    selector: &MessageSelector,
+    from_committed: bool,
+    hex: bool,
+    output: OutputFormat,
 ) -> Result<(), anyhow::Error> {
-    let context = CustomContext;
-    let mut config = ClientConfig::new();
+    let mode = if from_committed { ScanMode::FromCommitted } else { ScanMode::Browse };
+    let mut scanner = selector.scanner(settings, topic, mode)?;
 
-    config
-        .set(GROUP_ID, group_id)
-        .set(BOOTSTRAP_SERVERS, brokers)
-        .set(ENABLE_PARTITION_EOF, "true")  // Enable EOF detection
-        .set(AUTO_OFFSET_RESET, "earliest") // Start from beginning
-        .set(ENABLE_AUTO_COMMIT, "false")   // Don't auto-commit
-        .set(SESSION_TIMEOUT_MS, TIMEOUT_MS)
-        .set_log_level(RDKafkaLogLevel::Debug);
+    let records = selector.find_all(&mut scanner).await?;
+    if records.is_empty() {
+        return Err(anyhow!("Message not found ({})", scanner.summary()));
+    }
 
-    let consumer: LoggingConsumer = config
-        .create_with_context(context)
-        .context("Consumer creation failed")?;
+    if !output.is_table() {
+        let mapping = scanner.mapping();
+        let documents: Vec<serde_json::Value> = records
+            .iter()
+            .map(|record| {
+                let mut document = envelope::document(record, mapping);
+                if hex {
+                    document["payload"] = envelope::payload_hex(record.payload.as_deref()).into();
+                }
+                document
+            })
+            .collect();
+        return print_records(output, envelope::DOCUMENT_FIELDS, &documents);
+    }
 
-    let topics = &[topic];
-    consumer
-        .subscribe(topics)
-        .map_err(|e| anyhow!("Failed to subscribe to topic: {}", e))?;
+    let count = records.len();
+    for (index, record) in records.into_iter().enumerate() {
+        if count > 1 {
+            println!("\n{}:{}:{} ({}/{})", record.topic, record.partition, record.offset, index + 1, count);
+        }
+        print_record(record, hex);
+    }
+    Ok(())
+}
 
-    let mut table_data:Vec<DlqMessage> = vec!();
-    loop {
-        match consumer.recv().await {
-            Err(e) => {
-                if let rdkafka::error::KafkaError::PartitionEOF(_) = e {
-                    info!("Reached end of partition (EOF)");
-                    break
-                } else {
-                    warn!("Kafka error: {}", e);
-                    break; // Exit on other errors
-                }
-            }
-            Ok(m) => {
-                let payload = match m.payload_view::<str>() {
-                    None => {
-                        warn!("No payload found in message, skipping");
-                        continue;
-                    },
-                    Some(Ok(s)) => s,
-                    Some(Err(e)) => {
-                        warn!("Error while deserializing message payload: {:?}", e);
-                        ""
-                    }
-                };
-                
-                let json: serde_json::Value = match from_str(payload) {
-                    Ok(j) => j,
-                    Err(e) => {
-                        warn!("Failed to parse message as JSON: {}, skipping", e);
-                        continue;
-                    }
-                };
+fn print_record(record: DlqRecord, hex: bool) {
+    if record.parse_error.is_some() {
+        let mut table = Table::new(vec![UnparseableRecord::from(&record)]);
+        table.with(Rotate::Left);
+        println!("{table}");
 
-                let item = DlqMessage::parse(json);
-                if item.id.eq(id) || item.correlation_id.eq(id) {
-                    table_data.push(item);
-                    break;
-                }
-            }
-        };
+        println!("\nPayload:");
+        if hex {
+            println!("{}", envelope::payload_hex(record.payload.as_deref()));
+        } else {
+            let payload = record.payload.as_deref().map(String::from_utf8_lossy);
+            println!("{}", payload.as_deref().unwrap_or("-"));
+        }
+        return;
     }
 
-    if table_data.is_empty() {
-        return Err(anyhow!("Message not found"));
+    let mut message = record.message;
+    if hex {
+        message.payload = envelope::payload_hex(record.payload.as_deref());
     }
 
-    let mut table = Table::new(table_data);
+    let mut table = Table::new(vec![message]);
     table.with(Rotate::Left);
     println!("{table}");
-    Ok(())
 }
-
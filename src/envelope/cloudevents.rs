@@ -0,0 +1,135 @@
+use anyhow::Context;
+use serde_json::{Map, Value};
+
+use crate::{
+    cli::DlqMessage,
+    envelope::{format_timestamp, header_metadata, payload_bytes, payload_text, EnvelopeFormat, OutgoingRecord},
+    kafka::DlqRecord,
+};
+
+// Kafka protocol binding: binary mode puts attributes in `ce_` headers
+pub const CE_HEADER_PREFIX: &str = "ce_";
+pub const CE_HEADER_SPECVERSION: &str = "ce_specversion";
+pub const CE_HEADER_ID: &str = "ce_id";
+pub const CE_HEADER_TYPE: &str = "ce_type";
+pub const CE_HEADER_SOURCE: &str = "ce_source";
+pub const CE_HEADER_TIME: &str = "ce_time";
+pub const CE_HEADER_ERROR_DATA: &str = "ce_knativeerrordata";
+
+// Structured mode attributes
+pub const CE_SPECVERSION: &str = "specversion";
+pub const CE_ID: &str = "id";
+pub const CE_TYPE: &str = "type";
+pub const CE_SOURCE: &str = "source";
+pub const CE_TIME: &str = "time";
+pub const CE_DATA: &str = "data";
+pub const CE_DATA_BASE64: &str = "data_base64";
+// Extension set by Knative when it dead-letters an event
+pub const CE_ERROR_DATA: &str = "knativeerrordata";
+
+/// Whether the record is a CloudEvent in binary mode.
+pub fn is_binary(record: &DlqRecord) -> bool {
+    record.header(CE_HEADER_SPECVERSION).is_some()
+}
+
+/// Whether the record is a CloudEvent in binary or structured mode.
+pub fn matches(record: &DlqRecord) -> bool {
+    is_binary(record) || structured(record).is_some()
+}
+
+/// The event of a structured mode record.
+fn structured(record: &DlqRecord) -> Option<Map<String, Value>> {
+    let json: Value = serde_json::from_slice(record.payload.as_deref()?).ok()?;
+    match json {
+        Value::Object(event) if event.get(CE_SPECVERSION).is_some_and(Value::is_string) => {
+            Some(event)
+        }
+        _ => None,
+    }
+}
+
+/// Decode the event attributes. In binary mode the payload is the event
+/// data; in structured mode it is the whole event.
+pub fn decode(record: &mut DlqRecord) -> Result<(), String> {
+    let message = if is_binary(record) {
+        let text = |key: &str| record.header(key).map(|v| String::from_utf8_lossy(v).into_owned());
+        let time = text(CE_HEADER_TIME).or_else(|| record.timestamp.map(format_timestamp));
+        let message = DlqMessage {
+            id: text(CE_HEADER_ID).unwrap_or_else(|| "-".to_string()),
+            event_type: text(CE_HEADER_TYPE),
+            event_source: text(CE_HEADER_SOURCE),
+            reason: text(CE_HEADER_ERROR_DATA).unwrap_or_else(|| "-".to_string()),
+            moved_at: time.unwrap_or_else(|| "-".to_string()),
+            metadata: header_metadata(record, CE_HEADER_PREFIX, |_, _| None),
+            payload: payload_text(record.payload.as_deref()),
+            ..Default::default()
+        };
+        record.envelope = record
+            .payload
+            .as_deref()
+            .and_then(|payload| serde_json::from_slice(payload).ok())
+            .unwrap_or(Value::Null);
+        message
+    } else {
+        let event = structured(record).ok_or("payload is not a structured CloudEvent")?;
+        let text = |key: &str| event.get(key).and_then(Value::as_str).map(str::to_string);
+        let time = text(CE_TIME).or_else(|| record.timestamp.map(format_timestamp));
+
+        let attributes: Map<String, Value> = event
+            .iter()
+            .filter(|(key, _)| *key != CE_DATA && *key != CE_DATA_BASE64)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let data = event
+            .get(CE_DATA)
+            .or_else(|| event.get(CE_DATA_BASE64))
+            .unwrap_or(&Value::Null);
+
+        let message = DlqMessage {
+            id: text(CE_ID).unwrap_or_else(|| "-".to_string()),
+            event_type: text(CE_TYPE),
+            event_source: text(CE_SOURCE),
+            reason: text(CE_ERROR_DATA).unwrap_or_else(|| "-".to_string()),
+            moved_at: time.unwrap_or_else(|| "-".to_string()),
+            metadata: serde_json::to_string_pretty(&attributes).unwrap_or_else(|_| "-".to_string()),
+            payload: serde_json::to_string_pretty(data).unwrap_or_else(|_| "-".to_string()),
+            ..Default::default()
+        };
+        record.envelope = Value::Object(event);
+        message
+    };
+
+    record.format = EnvelopeFormat::CloudEvents;
+    record.message = DlqMessage {
+        correlation_id: "-".to_string(),
+        original_topic: "-".to_string(),
+        ..message
+    };
+    Ok(())
+}
+
+/// The record as it was read. Only the event data changes when a new
+/// payload is given; every attribute is kept as is.
+pub fn redrive(
+    record: &DlqRecord,
+    new_payload: Option<&Value>,
+) -> Result<OutgoingRecord, anyhow::Error> {
+    let payload = match new_payload {
+        None => record.payload.clone().unwrap_or_default(),
+        Some(payload) if is_binary(record) => payload_bytes(payload)?,
+        Some(payload) => {
+            let mut event = record.envelope.clone();
+            if let Value::Object(event) = &mut event {
+                event.remove(CE_DATA_BASE64);
+                event.insert(CE_DATA.to_string(), payload.clone());
+            }
+            serde_json::to_vec(&event).context("Failed to serialize the CloudEvent")?
+        }
+    };
+
+    Ok(OutgoingRecord {
+        key: record.key.clone(),
+        payload,
+        headers: record.headers.clone(),
+    })
+}
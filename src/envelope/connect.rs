@@ -0,0 +1,85 @@
+use serde_json::Value;
+
+use crate::{
+    cli::DlqMessage,
+    envelope::{
+        format_timestamp, header_metadata, payload_text, redrive_without_headers, EnvelopeFormat,
+        OutgoingRecord,
+    },
+    kafka::DlqRecord,
+};
+
+// Headers written by Kafka Connect when
+// errors.deadletterqueue.context.headers.enable=true
+pub const CONNECT_HEADER_PREFIX: &str = "__connect.errors.";
+pub const CONNECT_TOPIC: &str = "__connect.errors.topic";
+pub const CONNECT_PARTITION: &str = "__connect.errors.partition";
+pub const CONNECT_OFFSET: &str = "__connect.errors.offset";
+pub const CONNECT_CONNECTOR_NAME: &str = "__connect.errors.connector.name";
+pub const CONNECT_TASK_ID: &str = "__connect.errors.task.id";
+pub const CONNECT_EXCEPTION_CLASS: &str = "__connect.errors.exception.class.name";
+pub const CONNECT_EXCEPTION_MESSAGE: &str = "__connect.errors.exception.message";
+
+// Headers holding numbers (Connect writes all of them as strings)
+const NUMERIC_HEADERS: &[&str] = &[CONNECT_PARTITION, CONNECT_OFFSET, CONNECT_TASK_ID];
+
+/// Whether the record carries Kafka Connect error context headers.
+pub fn matches(record: &DlqRecord) -> bool {
+    record.header(CONNECT_TOPIC).is_some()
+}
+
+/// Decode the failure context from `__connect.errors.*` headers. The payload
+/// is the original record's value and is kept as is.
+pub fn decode(record: &mut DlqRecord) -> Result<(), String> {
+    let text = |key: &str| {
+        record
+            .header(key)
+            .map(|v| String::from_utf8_lossy(v).trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+
+    let reason = text(CONNECT_EXCEPTION_MESSAGE)
+        .or_else(|| text(CONNECT_EXCEPTION_CLASS))
+        .unwrap_or_else(|| "-".to_string());
+
+    let metadata = header_metadata(record, CONNECT_HEADER_PREFIX, |key, value| {
+        if NUMERIC_HEADERS.contains(&key) {
+            std::str::from_utf8(value).ok()?.trim().parse::<i64>().ok().map(Value::from)
+        } else {
+            None
+        }
+    });
+
+    let message = DlqMessage {
+        id: record.key_text().unwrap_or_else(|| "-".to_string()),
+        correlation_id: "-".to_string(),
+        reason,
+        retries: 0,
+        original_topic: text(CONNECT_TOPIC).unwrap_or_else(|| "-".to_string()),
+        original_partition: text(CONNECT_PARTITION).and_then(|p| p.parse().ok()),
+        original_offset: text(CONNECT_OFFSET).and_then(|o| o.parse().ok()),
+        connector: text(CONNECT_CONNECTOR_NAME),
+        task: text(CONNECT_TASK_ID),
+        moved_at: record.timestamp.map(format_timestamp).unwrap_or_else(|| "-".to_string()),
+        metadata,
+        payload: payload_text(record.payload.as_deref()),
+        ..Default::default()
+    };
+
+    record.format = EnvelopeFormat::Connect;
+    record.envelope = record
+        .payload
+        .as_deref()
+        .and_then(|payload| serde_json::from_slice(payload).ok())
+        .unwrap_or(Value::Null);
+    record.message = message;
+    Ok(())
+}
+
+/// The original key, value and headers, without the error context headers.
+pub fn redrive(
+    record: &DlqRecord,
+    new_payload: Option<&Value>,
+) -> Result<OutgoingRecord, anyhow::Error> {
+    redrive_without_headers(record, new_payload, CONNECT_HEADER_PREFIX)
+}
@@ -0,0 +1,85 @@
+use anyhow::Context;
+use serde_json::Value;
+
+use crate::{
+    cli::{DlqMessage, EnvelopeMapping},
+    envelope::{archive_headers, format_timestamp, EnvelopeFormat, OutgoingRecord},
+    kafka::DlqRecord,
+};
+
+/// Whether the body is a JSON object with an id or payload where the
+/// mapping expects them.
+pub fn matches(record: &DlqRecord, mapping: &EnvelopeMapping) -> bool {
+    let Some(json) = record
+        .payload
+        .as_deref()
+        .and_then(|payload| serde_json::from_slice::<Value>(payload).ok())
+    else {
+        return false;
+    };
+    json.is_object()
+        && (json.pointer(&mapping.id).is_some() || json.pointer(&mapping.payload).is_some())
+}
+
+/// Decode the README's JSON envelope.
+pub fn decode(record: &mut DlqRecord, mapping: &EnvelopeMapping) -> Result<(), String> {
+    let payload = match record.payload.as_deref() {
+        None => return Err("no payload".to_string()),
+        Some(payload) => std::str::from_utf8(payload)
+            .map_err(|e| format!("payload is not valid UTF-8: {:?}", e))?,
+    };
+
+    let json: Value =
+        serde_json::from_str(payload).map_err(|e| format!("payload is not valid JSON: {}", e))?;
+
+    record.format = EnvelopeFormat::Json;
+    record.message = DlqMessage::parse(json.clone(), mapping);
+    record.envelope = json;
+    Ok(())
+}
+
+/// The envelope as it was read, with the payload field replaced if requested.
+pub fn redrive(
+    record: &DlqRecord,
+    new_payload: Option<&Value>,
+    mapping: &EnvelopeMapping,
+) -> Result<OutgoingRecord, anyhow::Error> {
+    let mut envelope = record.envelope.clone();
+    if let Some(new_payload) = new_payload {
+        EnvelopeMapping::set(&mut envelope, &mapping.payload, new_payload.clone());
+    }
+
+    Ok(OutgoingRecord {
+        key: Some(record.message.id.as_bytes().to_vec()),
+        payload: serde_json::to_vec(&envelope).context("Failed to serialize message")?,
+        headers: vec![],
+    })
+}
+
+/// The envelope with the archive time and the record's original timestamp
+/// in its metadata, under the original key.
+pub fn archive(
+    record: &DlqRecord,
+    archived_at: &str,
+    mapping: &EnvelopeMapping,
+) -> Result<OutgoingRecord, anyhow::Error> {
+    let mut envelope = record.envelope.clone();
+    EnvelopeMapping::set(
+        &mut envelope,
+        &mapping.archived_at,
+        Value::String(archived_at.to_string()),
+    );
+    if let Some(timestamp) = record.timestamp {
+        EnvelopeMapping::set(
+            &mut envelope,
+            &mapping.original_timestamp,
+            Value::String(format_timestamp(timestamp)),
+        );
+    }
+
+    Ok(OutgoingRecord {
+        key: record.key.clone(),
+        payload: serde_json::to_vec(&envelope).context("Failed to serialize the message")?,
+        headers: archive_headers(record),
+    })
+}
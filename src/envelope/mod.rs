@@ -0,0 +1,437 @@
+pub mod cloudevents;
+pub mod connect;
+pub mod json;
+pub mod raw;
+pub mod spring;
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    cli::EnvelopeMapping,
+    kafka::{
+        DlqRecord, RecordHeader, MESSAGE_HEADER_ARCHIVED_AT, MESSAGE_HEADER_ORIGINAL_TIMESTAMP,
+        MESSAGE_HEADER_SOURCE_OFFSET, MESSAGE_HEADER_SOURCE_PARTITION, MESSAGE_HEADER_SOURCE_TOPIC,
+    },
+};
+
+/// DLQ conventions the tool knows how to decode. Configured per topic as
+/// `format = "json" | "spring" | "connect" | "cloudevents" | "raw"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvelopeFormat {
+    /// The JSON envelope described in the README, with context in `metadata`.
+    Json,
+    /// Spring Kafka's `DeadLetterPublishingRecoverer`: payload untouched,
+    /// failure context in `kafka_dlt-*` headers.
+    #[serde(rename = "spring")]
+    SpringDlt,
+    /// Kafka Connect `errors.deadletterqueue` with context headers enabled:
+    /// payload untouched, failure context in `__connect.errors.*` headers.
+    Connect,
+    /// CloudEvents in structured mode (the event as a JSON body) or binary
+    /// mode (attributes in `ce_*` headers).
+    CloudEvents,
+    /// Anything else: the record is shown as it is, without failure context.
+    Raw,
+}
+
+impl fmt::Display for EnvelopeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EnvelopeFormat::Json => "json",
+            EnvelopeFormat::SpringDlt => "spring",
+            EnvelopeFormat::Connect => "connect",
+            EnvelopeFormat::CloudEvents => "cloudevents",
+            EnvelopeFormat::Raw => "raw",
+        })
+    }
+}
+
+/// A record to be produced, e.g. when republishing or archiving.
+#[derive(Debug, Clone)]
+pub struct OutgoingRecord {
+    pub key: Option<Vec<u8>>,
+    pub payload: Vec<u8>,
+    pub headers: Vec<RecordHeader>,
+}
+
+// Formats in the order they win ties during detection
+const DETECTION_ORDER: [EnvelopeFormat; 5] = [
+    EnvelopeFormat::SpringDlt,
+    EnvelopeFormat::Connect,
+    EnvelopeFormat::CloudEvents,
+    EnvelopeFormat::Json,
+    EnvelopeFormat::Raw,
+];
+
+/// The format of a single record, judged by its headers and body.
+pub fn classify(record: &DlqRecord, mapping: &EnvelopeMapping) -> EnvelopeFormat {
+    if spring::matches(record) {
+        EnvelopeFormat::SpringDlt
+    } else if connect::matches(record) {
+        EnvelopeFormat::Connect
+    } else if cloudevents::matches(record) {
+        EnvelopeFormat::CloudEvents
+    } else if json::matches(record, mapping) {
+        EnvelopeFormat::Json
+    } else {
+        EnvelopeFormat::Raw
+    }
+}
+
+/// The format most of the sampled records use, or `None` for an empty sample.
+pub fn detect(records: &[DlqRecord], mapping: &EnvelopeMapping) -> Option<EnvelopeFormat> {
+    let formats: Vec<EnvelopeFormat> = records.iter().map(|r| classify(r, mapping)).collect();
+    DETECTION_ORDER
+        .iter()
+        .map(|format| (*format, formats.iter().filter(|f| *f == format).count()))
+        .filter(|(_, count)| *count > 0)
+        // max_by_key keeps the last maximum, so walk the order backwards
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(format, _)| format)
+}
+
+/// Decode a DLQ record in place, filling in its format, envelope and message.
+pub fn decode(
+    record: &mut DlqRecord,
+    format: EnvelopeFormat,
+    mapping: &EnvelopeMapping,
+) -> Result<(), String> {
+    match format {
+        EnvelopeFormat::Json => json::decode(record, mapping),
+        EnvelopeFormat::SpringDlt => spring::decode(record),
+        EnvelopeFormat::Connect => connect::decode(record),
+        EnvelopeFormat::CloudEvents => cloudevents::decode(record),
+        EnvelopeFormat::Raw => raw::decode(record, mapping),
+    }
+}
+
+/// The record to send back to the original topic, with the payload
+/// optionally replaced.
+pub fn redrive(
+    record: &DlqRecord,
+    new_payload: Option<&Value>,
+    mapping: &EnvelopeMapping,
+) -> Result<OutgoingRecord, anyhow::Error> {
+    match record.format {
+        EnvelopeFormat::Json => json::redrive(record, new_payload, mapping),
+        EnvelopeFormat::SpringDlt => spring::redrive(record, new_payload),
+        EnvelopeFormat::Connect => connect::redrive(record, new_payload),
+        EnvelopeFormat::CloudEvents => cloudevents::redrive(record, new_payload),
+        EnvelopeFormat::Raw => raw::redrive(record, new_payload),
+    }
+}
+
+/// The record to write to the archive topic.
+pub fn archive(
+    record: &DlqRecord,
+    archived_at: &str,
+    mapping: &EnvelopeMapping,
+) -> Result<OutgoingRecord, anyhow::Error> {
+    // Records that could not be decoded are archived byte for byte
+    if record.parse_error.is_some() {
+        return archive_with_header(record, archived_at);
+    }
+    match record.format {
+        EnvelopeFormat::Json => json::archive(record, archived_at, mapping),
+        EnvelopeFormat::SpringDlt
+        | EnvelopeFormat::Connect
+        | EnvelopeFormat::CloudEvents
+        | EnvelopeFormat::Raw => archive_with_header(record, archived_at),
+    }
+}
+
+/// The original payload as JSON: the envelope's payload field, the data of
+/// a structured CloudEvent, or the decoded record value. Values that are not
+/// JSON are given as text.
+pub fn payload_json(record: &DlqRecord, mapping: &EnvelopeMapping) -> Value {
+    let payload = match record.format {
+        EnvelopeFormat::Json => record.envelope.pointer(&mapping.payload),
+        EnvelopeFormat::CloudEvents if !cloudevents::is_binary(record) => {
+            record.envelope.get(cloudevents::CE_DATA)
+        }
+        _ => Some(&record.envelope).filter(|envelope| !envelope.is_null()),
+    };
+    match (payload, record.format, record.payload.as_deref()) {
+        (Some(payload), _, _) => payload.clone(),
+        (None, EnvelopeFormat::Json | EnvelopeFormat::CloudEvents, _) | (None, _, None) => {
+            Value::Null
+        }
+        (None, _, Some(bytes)) => Value::String(String::from_utf8_lossy(bytes).into_owned()),
+    }
+}
+
+/// Top-level fields of `document`.
+pub const DOCUMENT_FIELDS: &[&str] = &[
+    "id", "correlation_id", "reason", "retries", "original_topic", "original_partition",
+    "original_offset", "moved_at", "connector", "task", "type", "source", "topic", "partition",
+    "offset", "key", "timestamp", "format", "headers", "metadata", "payload", "parse_error",
+];
+
+// Fields of `document` that come from the decoded message
+const MESSAGE_FIELDS: &[&str] = &[
+    "id", "correlation_id", "reason", "retries", "original_topic", "original_partition",
+    "original_offset", "moved_at", "connector", "task", "type", "source", "metadata",
+];
+
+/// A JSON view of a decoded record that selectors and filters are evaluated
+/// against. Field names follow `DlqMessage`; `headers` maps names to text.
+pub fn document(record: &DlqRecord, mapping: &EnvelopeMapping) -> Value {
+    let message = &record.message;
+    let headers: Map<String, Value> = record
+        .headers
+        .iter()
+        .map(|header| {
+            let value = header.value.as_deref().unwrap_or_default();
+            (header.key.clone(), Value::String(String::from_utf8_lossy(value).into_owned()))
+        })
+        .collect();
+
+    let mut document = serde_json::json!({
+        "id": message.id,
+        "correlation_id": message.correlation_id,
+        "reason": message.reason,
+        "retries": message.retries,
+        "original_topic": message.original_topic,
+        "original_partition": message.original_partition,
+        "original_offset": message.original_offset,
+        "moved_at": message.moved_at,
+        "connector": message.connector,
+        "task": message.task,
+        "type": message.event_type,
+        "source": message.event_source,
+        "topic": record.topic,
+        "partition": record.partition,
+        "offset": record.offset,
+        "key": record.key_text(),
+        "timestamp": record.timestamp.map(format_timestamp),
+        "format": record.format.to_string(),
+        "headers": headers,
+        "metadata": serde_json::from_str::<Value>(&message.metadata).unwrap_or(Value::Null),
+        "payload": payload_json(record, mapping),
+        "parse_error": record.parse_error,
+    });
+    // An undecoded record has no message fields
+    if record.parse_error.is_some() {
+        for field in MESSAGE_FIELDS {
+            document[*field] = Value::Null;
+        }
+    }
+    document
+}
+
+/// Payload for display: pretty JSON when it parses, text when it is UTF-8,
+/// otherwise a hex dump.
+pub fn payload_text(payload: Option<&[u8]>) -> String {
+    let Some(payload) = payload else {
+        return "-".to_string();
+    };
+    if let Ok(json) = serde_json::from_slice::<Value>(payload) {
+        return serde_json::to_string_pretty(&json).unwrap_or_else(|_| "-".to_string());
+    }
+    match std::str::from_utf8(payload) {
+        Ok(text) => text.to_string(),
+        Err(_) => payload_hex(Some(payload)),
+    }
+}
+
+/// Hex dump of the payload, 16 bytes per line with their offset and the
+/// printable ASCII characters.
+pub fn payload_hex(payload: Option<&[u8]>) -> String {
+    let Some(payload) = payload else {
+        return "-".to_string();
+    };
+    payload
+        .chunks(16)
+        .enumerate()
+        .map(|(line, bytes)| {
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = bytes
+                .iter()
+                .map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' })
+                .collect();
+            format!("{:08x}  {:<47}  {}", line * 16, hex.join(" "), ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The record's headers, followed by the DLQ topic, partition and offset it
+/// is archived from.
+pub fn archive_headers(record: &DlqRecord) -> Vec<RecordHeader> {
+    let mut headers = record.headers.clone();
+    headers.push(text_header(MESSAGE_HEADER_SOURCE_TOPIC, &record.topic));
+    headers.push(text_header(MESSAGE_HEADER_SOURCE_PARTITION, &record.partition.to_string()));
+    headers.push(text_header(MESSAGE_HEADER_SOURCE_OFFSET, &record.offset.to_string()));
+    headers
+}
+
+fn text_header(key: &str, value: &str) -> RecordHeader {
+    RecordHeader {
+        key: key.to_string(),
+        value: Some(value.as_bytes().to_vec()),
+    }
+}
+
+/// Milliseconds since the epoch as RFC 3339.
+pub fn format_timestamp(millis: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(millis)
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_else(|| millis.to_string())
+}
+
+/// Bytes to publish for a payload given as JSON. Strings are sent as plain
+/// text so that non-JSON payloads can be replaced too.
+pub fn payload_bytes(payload: &Value) -> Result<Vec<u8>, anyhow::Error> {
+    match payload {
+        Value::String(text) => Ok(text.as_bytes().to_vec()),
+        json => Ok(serde_json::to_vec(json)?),
+    }
+}
+
+/// Pretty JSON object of the headers starting with `prefix`, keyed by the
+/// rest of the header name. `number` may decode a value as a number;
+/// everything else is shown as text.
+pub fn header_metadata<F>(record: &DlqRecord, prefix: &str, number: F) -> String
+where
+    F: Fn(&str, &[u8]) -> Option<Value>,
+{
+    let mut metadata = Map::new();
+    for header in &record.headers {
+        let Some(name) = header.key.strip_prefix(prefix) else {
+            continue;
+        };
+        let value = header.value.as_deref().unwrap_or_default();
+        let value = number(&header.key, value)
+            .unwrap_or_else(|| Value::String(String::from_utf8_lossy(value).into_owned()));
+        metadata.insert(name.to_string(), value);
+    }
+    serde_json::to_string_pretty(&metadata).unwrap_or_else(|_| "-".to_string())
+}
+
+/// The original key, value and headers, minus the headers starting with
+/// `prefix` that the DLQ added.
+pub fn redrive_without_headers(
+    record: &DlqRecord,
+    new_payload: Option<&Value>,
+    prefix: &str,
+) -> Result<OutgoingRecord, anyhow::Error> {
+    let payload = match new_payload {
+        Some(payload) => payload_bytes(payload)?,
+        None => record.payload.clone().unwrap_or_default(),
+    };
+
+    Ok(OutgoingRecord {
+        key: record.key.clone(),
+        payload,
+        headers: record
+            .headers
+            .iter()
+            .filter(|header| !header.key.starts_with(prefix))
+            .cloned()
+            .collect(),
+    })
+}
+
+/// The record unchanged, with the archive time and original timestamp added
+/// as headers. Used for formats that keep their context in headers rather
+/// than the body.
+pub fn archive_with_header(
+    record: &DlqRecord,
+    archived_at: &str,
+) -> Result<OutgoingRecord, anyhow::Error> {
+    let mut headers = archive_headers(record);
+    headers.push(text_header(MESSAGE_HEADER_ARCHIVED_AT, archived_at));
+    if let Some(timestamp) = record.timestamp {
+        headers.push(text_header(MESSAGE_HEADER_ORIGINAL_TIMESTAMP, &format_timestamp(timestamp)));
+    }
+
+    Ok(OutgoingRecord {
+        key: record.key.clone(),
+        payload: record.payload.clone().unwrap_or_default(),
+        headers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(headers: &[&str], payload: &str) -> DlqRecord {
+        DlqRecord {
+            topic: "dlq".to_string(),
+            partition: 0,
+            offset: 0,
+            key: None,
+            headers: headers
+                .iter()
+                .map(|key| RecordHeader { key: key.to_string(), value: Some(b"x".to_vec()) })
+                .collect(),
+            timestamp: None,
+            payload: Some(payload.as_bytes().to_vec()),
+            format: EnvelopeFormat::Raw,
+            envelope: Value::Null,
+            payload_encoding: None,
+            parse_error: None,
+            message: Default::default(),
+        }
+    }
+
+    fn spring() -> DlqRecord {
+        record(&[spring::DLT_ORIGINAL_TOPIC], "not json")
+    }
+
+    fn connect() -> DlqRecord {
+        record(&[connect::CONNECT_TOPIC], "not json")
+    }
+
+    fn json() -> DlqRecord {
+        record(&[], r#"{"id": "1", "payload": {}}"#)
+    }
+
+    fn raw() -> DlqRecord {
+        record(&[], "not json")
+    }
+
+    fn detected(records: &[DlqRecord]) -> Option<EnvelopeFormat> {
+        detect(records, &EnvelopeMapping::default())
+    }
+
+    #[test]
+    fn classifies_single_records() {
+        let mapping = EnvelopeMapping::default();
+        assert_eq!(classify(&spring(), &mapping), EnvelopeFormat::SpringDlt);
+        assert_eq!(classify(&connect(), &mapping), EnvelopeFormat::Connect);
+        assert_eq!(classify(&record(&[], r#"{"specversion": "1.0", "id": "1"}"#), &mapping), EnvelopeFormat::CloudEvents);
+        assert_eq!(classify(&json(), &mapping), EnvelopeFormat::Json);
+        assert_eq!(classify(&record(&[], r#"{"other": 1}"#), &mapping), EnvelopeFormat::Raw);
+        assert_eq!(classify(&raw(), &mapping), EnvelopeFormat::Raw);
+    }
+
+    #[test]
+    fn nothing_is_detected_from_an_empty_sample() {
+        assert_eq!(detected(&[]), None);
+    }
+
+    #[test]
+    fn detects_the_majority_format() {
+        assert_eq!(detected(&[json(), json(), json()]), Some(EnvelopeFormat::Json));
+        assert_eq!(detected(&[json(), raw(), raw()]), Some(EnvelopeFormat::Raw));
+        assert_eq!(detected(&[raw(), connect(), spring(), connect()]), Some(EnvelopeFormat::Connect));
+
+        let mut sample: Vec<DlqRecord> = (0..11).map(|_| spring()).collect();
+        sample.extend((0..9).map(|_| json()));
+        assert_eq!(detected(&sample), Some(EnvelopeFormat::SpringDlt));
+    }
+
+    #[test]
+    fn ties_go_to_the_earlier_format_in_detection_order() {
+        assert_eq!(detected(&[json(), spring()]), Some(EnvelopeFormat::SpringDlt));
+        assert_eq!(detected(&[raw(), json()]), Some(EnvelopeFormat::Json));
+        assert_eq!(detected(&[raw(), connect(), json(), spring()]), Some(EnvelopeFormat::SpringDlt));
+    }
+}
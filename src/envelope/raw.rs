@@ -0,0 +1,58 @@
+use serde_json::Value;
+
+use crate::{
+    cli::{DlqMessage, EnvelopeMapping},
+    envelope::{format_timestamp, header_metadata, payload_bytes, payload_text, EnvelopeFormat, OutgoingRecord},
+    kafka::DlqRecord,
+};
+
+/// Decode a record that follows no known DLQ convention. The id, reason and
+/// original topic come from the configured headers; without an id header the
+/// record key is the id. Every header is shown as metadata.
+pub fn decode(record: &mut DlqRecord, mapping: &EnvelopeMapping) -> Result<(), String> {
+    let text = |key: &Option<String>| {
+        key.as_deref()
+            .and_then(|key| record.header(key))
+            .map(|v| String::from_utf8_lossy(v).into_owned())
+    };
+
+    let message = DlqMessage {
+        id: text(&mapping.raw.id_header)
+            .or_else(|| record.key_text())
+            .unwrap_or_else(|| "-".to_string()),
+        correlation_id: "-".to_string(),
+        reason: text(&mapping.raw.reason_header).unwrap_or_else(|| "-".to_string()),
+        original_topic: text(&mapping.raw.original_topic_header)
+            .unwrap_or_else(|| "-".to_string()),
+        moved_at: record.timestamp.map(format_timestamp).unwrap_or_else(|| "-".to_string()),
+        metadata: header_metadata(record, "", |_, _| None),
+        payload: payload_text(record.payload.as_deref()),
+        ..Default::default()
+    };
+
+    record.format = EnvelopeFormat::Raw;
+    record.envelope = record
+        .payload
+        .as_deref()
+        .and_then(|payload| serde_json::from_slice(payload).ok())
+        .unwrap_or(Value::Null);
+    record.message = message;
+    Ok(())
+}
+
+/// The record's key, value and headers as they are.
+pub fn redrive(
+    record: &DlqRecord,
+    new_payload: Option<&Value>,
+) -> Result<OutgoingRecord, anyhow::Error> {
+    let payload = match new_payload {
+        Some(payload) => payload_bytes(payload)?,
+        None => record.payload.clone().unwrap_or_default(),
+    };
+
+    Ok(OutgoingRecord {
+        key: record.key.clone(),
+        payload,
+        headers: record.headers.clone(),
+    })
+}
@@ -0,0 +1,103 @@
+use serde_json::Value;
+
+use crate::{
+    cli::DlqMessage,
+    envelope::{
+        format_timestamp, header_metadata, payload_text, redrive_without_headers, EnvelopeFormat,
+        OutgoingRecord,
+    },
+    kafka::DlqRecord,
+};
+
+// Headers written by Spring Kafka's DeadLetterPublishingRecoverer
+pub const DLT_HEADER_PREFIX: &str = "kafka_dlt-";
+pub const DLT_ORIGINAL_TOPIC: &str = "kafka_dlt-original-topic";
+pub const DLT_ORIGINAL_PARTITION: &str = "kafka_dlt-original-partition";
+pub const DLT_ORIGINAL_OFFSET: &str = "kafka_dlt-original-offset";
+pub const DLT_ORIGINAL_TIMESTAMP: &str = "kafka_dlt-original-timestamp";
+pub const DLT_EXCEPTION_FQCN: &str = "kafka_dlt-exception-fqcn";
+pub const DLT_EXCEPTION_MESSAGE: &str = "kafka_dlt-exception-message";
+// Attempt counter added by Spring's non-blocking retry topics
+pub const RETRY_TOPIC_ATTEMPTS: &str = "retry_topic-attempts";
+
+// Headers that Spring writes as big-endian binary numbers
+const NUMERIC_HEADERS: &[&str] = &[
+    DLT_ORIGINAL_PARTITION,
+    DLT_ORIGINAL_OFFSET,
+    DLT_ORIGINAL_TIMESTAMP,
+    RETRY_TOPIC_ATTEMPTS,
+];
+
+/// Whether the record carries Spring dead-letter headers.
+pub fn matches(record: &DlqRecord) -> bool {
+    record.header(DLT_ORIGINAL_TOPIC).is_some()
+}
+
+/// Decode the failure context from `kafka_dlt-*` headers. The payload is the
+/// original record's value and is kept as is.
+pub fn decode(record: &mut DlqRecord) -> Result<(), String> {
+    let text = |key: &str| record.header(key).map(|v| String::from_utf8_lossy(v).into_owned());
+
+    let reason = text(DLT_EXCEPTION_MESSAGE)
+        .filter(|message| !message.is_empty())
+        .or_else(|| text(DLT_EXCEPTION_FQCN))
+        .unwrap_or_else(|| "-".to_string());
+
+    let metadata = header_metadata(record, DLT_HEADER_PREFIX, |key, value| {
+        if NUMERIC_HEADERS.contains(&key) {
+            header_number(value).map(Value::from)
+        } else {
+            None
+        }
+    });
+
+    let message = DlqMessage {
+        id: record.key_text().unwrap_or_else(|| "-".to_string()),
+        correlation_id: "-".to_string(),
+        reason,
+        retries: record.header(RETRY_TOPIC_ATTEMPTS).and_then(header_number).unwrap_or(0),
+        original_topic: text(DLT_ORIGINAL_TOPIC).unwrap_or_else(|| "-".to_string()),
+        original_partition: record
+            .header(DLT_ORIGINAL_PARTITION)
+            .and_then(header_number)
+            .and_then(|p| i32::try_from(p).ok()),
+        original_offset: record.header(DLT_ORIGINAL_OFFSET).and_then(header_number),
+        moved_at: record.timestamp.map(format_timestamp).unwrap_or_else(|| "-".to_string()),
+        metadata,
+        payload: payload_text(record.payload.as_deref()),
+        ..Default::default()
+    };
+
+    record.format = EnvelopeFormat::SpringDlt;
+    record.envelope = record
+        .payload
+        .as_deref()
+        .and_then(|payload| serde_json::from_slice(payload).ok())
+        .unwrap_or(Value::Null);
+    record.message = message;
+    Ok(())
+}
+
+/// The original key, value and headers, without the dead-letter headers.
+pub fn redrive(
+    record: &DlqRecord,
+    new_payload: Option<&Value>,
+) -> Result<OutgoingRecord, anyhow::Error> {
+    redrive_without_headers(record, new_payload, DLT_HEADER_PREFIX)
+}
+
+/// Spring writes numbers as 4 or 8 byte big-endian values; header mappers
+/// may also write them as text.
+fn header_number(value: &[u8]) -> Option<i64> {
+    let is_text = !value.is_empty()
+        && value.iter().all(|b| b.is_ascii_digit() || *b == b'-');
+    if is_text {
+        return std::str::from_utf8(value).ok()?.parse().ok();
+    }
+
+    match value.len() {
+        4 => Some(i32::from_be_bytes(value.try_into().ok()?) as i64),
+        8 => Some(i64::from_be_bytes(value.try_into().ok()?)),
+        _ => None,
+    }
+}
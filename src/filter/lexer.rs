@@ -0,0 +1,196 @@
+use crate::filter::ParseError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Ident(String),
+    String(String),
+    Number(f64),
+    /// A number with a unit, e.g. `2d`, in seconds.
+    Duration(i64),
+    Dot,
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    Plus,
+    Minus,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    NotContains,
+    End,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Byte offset in the expression, for error carets.
+    pub position: usize,
+}
+
+impl TokenKind {
+    pub fn describe(&self) -> String {
+        match self {
+            TokenKind::Ident(name) => format!("`{}`", name),
+            TokenKind::String(text) => format!("\"{}\"", text),
+            TokenKind::Number(number) => format!("`{}`", number),
+            TokenKind::Duration(_) => "a duration".to_string(),
+            TokenKind::End => "end of expression".to_string(),
+            symbol => format!("`{}`", symbol.symbol()),
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            TokenKind::Dot => ".",
+            TokenKind::LBracket => "[",
+            TokenKind::RBracket => "]",
+            TokenKind::LParen => "(",
+            TokenKind::RParen => ")",
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Eq => "==",
+            TokenKind::Ne => "!=",
+            TokenKind::Lt => "<",
+            TokenKind::Le => "<=",
+            TokenKind::Gt => ">",
+            TokenKind::Ge => ">=",
+            TokenKind::Contains => "~",
+            TokenKind::NotContains => "!~",
+            _ => "",
+        }
+    }
+}
+
+// Seconds per duration unit, e.g. `30s`, `15m`, `2h`, `2d`, `1w`
+const DURATION_UNITS: &[(&str, i64)] = &[("s", 1), ("m", 60), ("h", 3600), ("d", 86400), ("w", 604800)];
+
+/// A duration such as `2d` or `30m`, in seconds.
+pub fn parse_duration(text: &str) -> Option<i64> {
+    let digits = text.find(|c: char| !c.is_ascii_digit())?;
+    let (_, seconds) = DURATION_UNITS.iter().find(|(unit, _)| *unit == &text[digits..])?;
+    text[..digits].parse::<i64>().ok()?.checked_mul(*seconds)
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let bytes = input.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let two = input.get(i..i + 2).unwrap_or_default();
+        let symbol = match two {
+            "==" => Some(TokenKind::Eq),
+            "!=" => Some(TokenKind::Ne),
+            "<=" => Some(TokenKind::Le),
+            ">=" => Some(TokenKind::Ge),
+            "!~" => Some(TokenKind::NotContains),
+            _ => None,
+        };
+        if let Some(kind) = symbol {
+            tokens.push(Token { kind, position: start });
+            i += 2;
+            continue;
+        }
+
+        let kind = match c {
+            b'.' => TokenKind::Dot,
+            b'[' => TokenKind::LBracket,
+            b']' => TokenKind::RBracket,
+            b'(' => TokenKind::LParen,
+            b')' => TokenKind::RParen,
+            b'+' => TokenKind::Plus,
+            b'-' => TokenKind::Minus,
+            b'<' => TokenKind::Lt,
+            b'>' => TokenKind::Gt,
+            b'~' => TokenKind::Contains,
+            b'=' => return Err(ParseError::new("expected `==`", start)),
+            b'"' => {
+                let (text, end) = string(input, start)?;
+                tokens.push(Token { kind: TokenKind::String(text), position: start });
+                i = end;
+                continue;
+            }
+            b'0'..=b'9' => {
+                let (kind, end) = number(input, start)?;
+                tokens.push(Token { kind, position: start });
+                i = end;
+                continue;
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                let end = input[start..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .map_or(input.len(), |len| start + len);
+                tokens.push(Token { kind: TokenKind::Ident(input[start..end].to_string()), position: start });
+                i = end;
+                continue;
+            }
+            _ => {
+                let found = input[start..].chars().next().unwrap_or_default();
+                return Err(ParseError::new(format!("unexpected character `{}`", found), start));
+            }
+        };
+        tokens.push(Token { kind, position: start });
+        i += 1;
+    }
+
+    tokens.push(Token { kind: TokenKind::End, position: input.len() });
+    Ok(tokens)
+}
+
+/// A double-quoted string starting at `start`, and the offset after it.
+fn string(input: &str, start: usize) -> Result<(String, usize), ParseError> {
+    let mut text = String::new();
+    let mut chars = input[start + 1..].char_indices();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '"' => return Ok((text, start + 1 + offset + 1)),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => text.push('\n'),
+                Some((_, 't')) => text.push('\t'),
+                Some((_, c @ ('"' | '\\'))) => text.push(c),
+                Some((offset, c)) => {
+                    return Err(ParseError::new(
+                        format!("unknown escape `\\{}`", c),
+                        start + 1 + offset,
+                    ))
+                }
+                None => break,
+            },
+            c => text.push(c),
+        }
+    }
+    Err(ParseError::new("unterminated string", start))
+}
+
+/// A number or duration starting at `start`, and the offset after it.
+fn number(input: &str, start: usize) -> Result<(TokenKind, usize), ParseError> {
+    let end = input[start..]
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.'))
+        .map_or(input.len(), |len| start + len);
+    let text = &input[start..end];
+
+    if let Some(seconds) = parse_duration(text) {
+        return Ok((TokenKind::Duration(seconds), end));
+    }
+
+    text.parse()
+        .map(|number| (TokenKind::Number(number), end))
+        .map_err(|_| {
+            ParseError::new(
+                format!("invalid number `{}` (durations use s, m, h, d or w, e.g. `2d`)", text),
+                start,
+            )
+        })
+}
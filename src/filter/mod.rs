@@ -0,0 +1,338 @@
+pub mod lexer;
+pub mod parser;
+pub mod template;
+
+pub use template::*;
+
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde_json::Value;
+
+use parser::{Expr, Literal, Op, Parser};
+
+/// A filter expression over the fields of a DLQ record, e.g.
+/// `retries >= 3 and reason ~ "timeout" and moved_at > now-2d`.
+/// It is evaluated against `envelope::document`.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    text: String,
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let tokens = lexer::tokenize(text)?;
+        let expr = Parser::new(tokens, Utc::now()).parse()?;
+        Ok(Self { text: text.to_string(), expr })
+    }
+
+    pub fn matches(&self, document: &Value) -> bool {
+        evaluate(&self.expr, document)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Filter::parse(text).map_err(|e| e.render(text))
+    }
+}
+
+/// A field of the record document as written in `--columns` and templates:
+/// a path as in filter expressions, e.g. `payload.userId`, or `header:NAME`.
+#[derive(Debug, Clone)]
+pub struct Field {
+    name: String,
+    pointer: String,
+}
+
+impl Field {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let pointer = match text.strip_prefix("header:") {
+            Some("") => return Err(ParseError::new("missing header name", text.len())),
+            Some(header) => format!("/headers/{}", header.replace('~', "~0").replace('/', "~1")),
+            None => Parser::new(lexer::tokenize(text)?, Utc::now()).parse_path()?,
+        };
+        Ok(Self { name: text.to_string(), pointer })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The field's value in `document`, null if it is missing.
+    pub fn value(&self, document: &Value) -> Value {
+        document.pointer(&self.pointer).cloned().unwrap_or(Value::Null)
+    }
+
+    /// The field's value in `document`, `None` if it is missing or null.
+    /// Strings are returned as they are, other values as JSON.
+    pub fn text(&self, document: &Value) -> Option<String> {
+        text(document.pointer(&self.pointer)?)
+    }
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Field::parse(text).map_err(|e| e.render(text))
+    }
+}
+
+/// A syntax error at a byte offset in the expression.
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl ParseError {
+    pub fn new(message: impl Into<String>, position: usize) -> Self {
+        Self { message: message.into(), position }
+    }
+
+    /// The message with the expression and a caret under the error.
+    pub fn render(&self, text: &str) -> String {
+        let column = text[..self.position.min(text.len())].chars().count();
+        format!("{}\n  {}\n  {}^", self.message, text, " ".repeat(column))
+    }
+}
+
+fn evaluate(expr: &Expr, document: &Value) -> bool {
+    match expr {
+        Expr::And(left, right) => evaluate(left, document) && evaluate(right, document),
+        Expr::Or(left, right) => evaluate(left, document) || evaluate(right, document),
+        Expr::Not(inner) => !evaluate(inner, document),
+        Expr::Compare { pointer, op, value } => {
+            let actual = document.pointer(pointer).unwrap_or(&Value::Null);
+            compare(actual, *op, value)
+        }
+    }
+}
+
+fn compare(actual: &Value, op: Op, literal: &Literal) -> bool {
+    match op {
+        Op::Eq => equals(actual, literal),
+        Op::Ne => !equals(actual, literal),
+        Op::Contains | Op::NotContains => {
+            let Literal::String(needle) = literal else { return false };
+            let found = text(actual)
+                .is_some_and(|text| text.to_lowercase().contains(&needle.to_lowercase()));
+            found == (op == Op::Contains)
+        }
+        Op::Lt | Op::Le | Op::Gt | Op::Ge => {
+            let Some(ordering) = order(actual, literal) else { return false };
+            match op {
+                Op::Lt => ordering == Ordering::Less,
+                Op::Le => ordering != Ordering::Greater,
+                Op::Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            }
+        }
+    }
+}
+
+fn equals(actual: &Value, literal: &Literal) -> bool {
+    match literal {
+        Literal::Null => actual.is_null(),
+        Literal::Bool(expected) => actual.as_bool() == Some(*expected),
+        Literal::Number(expected) => number(actual) == Some(*expected),
+        Literal::String(expected) => !actual.is_null() && text(actual).as_deref() == Some(expected.as_str()),
+        Literal::Time(expected) => time(actual) == Some(*expected),
+    }
+}
+
+fn order(actual: &Value, literal: &Literal) -> Option<Ordering> {
+    match literal {
+        Literal::Number(expected) => number(actual)?.partial_cmp(expected),
+        Literal::Time(expected) => Some(time(actual)?.cmp(expected)),
+        // Timestamps written as strings compare as times, other strings as text
+        Literal::String(expected) => match parse_time(expected) {
+            Some(expected) => Some(time(actual)?.cmp(&expected)),
+            None => Some(actual.as_str()?.cmp(expected.as_str())),
+        },
+        Literal::Null | Literal::Bool(_) => None,
+    }
+}
+
+/// Numbers, and strings holding a number (e.g. header values).
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(text) => Some(text.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Timestamp strings, or epoch milliseconds.
+fn time(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(text) => parse_time(text),
+        Value::Number(number) => DateTime::from_timestamp_millis(number.as_i64()?),
+        _ => None,
+    }
+}
+
+/// A timestamp as found in DLQ envelopes: RFC 3339, the same without an
+/// offset (taken as UTC), or a date.
+pub fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Some(time.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(time.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| time.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use serde_json::json;
+
+    use super::*;
+
+    fn document(retries: i64, reason: &str, moved_at: DateTime<Utc>) -> Value {
+        json!({
+            "id": "m-1",
+            "retries": retries,
+            "reason": reason,
+            "moved_at": moved_at.to_rfc3339(),
+            "headers": { "attempt": "2" },
+            "payload": { "userId": "user-123", "data": { "email": null } },
+        })
+    }
+
+    fn matches(expr: &str, document: &Value) -> bool {
+        Filter::parse(expr).unwrap_or_else(|e| panic!("{}", e.render(expr))).matches(document)
+    }
+
+    fn error(expr: &str) -> ParseError {
+        Filter::parse(expr).expect_err("the expression should not parse")
+    }
+
+    #[test]
+    fn evaluates_the_documented_example() {
+        let expr = r#"retries >= 3 and reason ~ "timeout" and moved_at > now-2d and payload.data.email == null"#;
+        let recent = Utc::now() - Duration::hours(1);
+
+        assert!(matches(expr, &document(3, "Read Timeout", recent)));
+        assert!(!matches(expr, &document(2, "Read Timeout", recent)));
+        assert!(!matches(expr, &document(5, "Bad request", recent)));
+        assert!(!matches(expr, &document(5, "Read Timeout", recent - Duration::days(3))));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let doc = document(1, "timeout", Utc::now());
+
+        // a or (b and c)
+        assert!(matches(r#"retries == 1 or retries == 2 and reason == "x""#, &doc));
+        assert!(!matches(r#"(retries == 1 or retries == 2) and reason == "x""#, &doc));
+        assert!(matches(r#"reason == "x" and retries == 2 or retries == 1"#, &doc));
+    }
+
+    #[test]
+    fn not_applies_to_the_next_comparison_or_group() {
+        let doc = document(1, "timeout", Utc::now());
+
+        assert!(matches(r#"not retries == 2 and reason == "timeout""#, &doc));
+        assert!(!matches(r#"not (retries == 1 and reason == "timeout")"#, &doc));
+        assert!(matches("not not retries == 1", &doc));
+        assert!(matches(r#"NOT retries > 5 AND reason ~ "TIME""#, &doc));
+    }
+
+    #[test]
+    fn null_matches_missing_and_null_fields() {
+        let doc = document(1, "timeout", Utc::now());
+
+        assert!(matches("payload.data.email == null", &doc));
+        assert!(matches("payload.missing == null", &doc));
+        assert!(matches("connector == null", &doc));
+        assert!(matches("payload.userId != null", &doc));
+        assert!(!matches("payload.userId == null", &doc));
+        // Ordering never matches a missing value
+        assert!(!matches("payload.missing > 0", &doc));
+        assert!(!matches("payload.missing < 0", &doc));
+    }
+
+    #[test]
+    fn contains_ignores_case() {
+        let doc = document(1, "Connection TIMEOUT after 30s", Utc::now());
+
+        assert!(matches(r#"reason ~ "timeout""#, &doc));
+        assert!(matches(r#"reason ~ "Connection timeout""#, &doc));
+        assert!(!matches(r#"reason !~ "TimeOut""#, &doc));
+        assert!(matches(r#"reason !~ "refused""#, &doc));
+        // A missing field contains nothing
+        assert!(!matches(r#"connector ~ "x""#, &doc));
+        assert!(matches(r#"connector !~ "x""#, &doc));
+    }
+
+    #[test]
+    fn coerces_numbers_and_times() {
+        let doc = document(10, "timeout", Utc::now());
+
+        // Numbers compare numerically, also when stored as text
+        assert!(matches("retries > 9", &doc));
+        assert!(matches("headers.attempt == 2", &doc));
+        assert!(matches("headers.attempt >= 1.5", &doc));
+        // Time strings compare as times
+        assert!(matches(r#"moved_at > "2020-01-01""#, &doc));
+        assert!(matches("moved_at < now+1h", &doc));
+    }
+
+    #[test]
+    fn reports_the_position_of_parse_errors() {
+        let cases = [
+            ("retries >", 9, "expected a value"),
+            ("retries >= 3 and", 16, "expected a field"),
+            ("retris > 3", 0, "unknown field `retris`"),
+            (r#"reason = "x""#, 7, "expected `==`"),
+            (r#"(retries > 3 and reason ~ "x""#, 0, "unclosed `(`"),
+            ("retries > 3)", 11, "unmatched `)`"),
+            ("reason ~ 3", 9, "`~` and `!~` need a string"),
+            ("reason == timeout", 10, "strings need double quotes"),
+            ("moved_at > now-999999999999d", 15, "duration is out of range"),
+        ];
+        for (expr, position, message) in cases {
+            let error = error(expr);
+            assert!(error.message.contains(message), "{}: {}", expr, error.message);
+            assert_eq!(error.position, position, "{}: {}", expr, error.message);
+        }
+    }
+
+    #[test]
+    fn renders_a_caret_under_the_error() {
+        let expr = "retries >= 3 and retris < 5";
+
+        let rendered = error(expr).render(expr);
+
+        assert_eq!(
+            rendered.lines().skip(1).collect::<Vec<_>>(),
+            ["  retries >= 3 and retris < 5", "                   ^"]
+        );
+    }
+}
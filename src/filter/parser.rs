@@ -0,0 +1,260 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    envelope::DOCUMENT_FIELDS,
+    filter::{
+        lexer::{Token, TokenKind},
+        ParseError,
+    },
+};
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare { pointer: String, op: Op, value: Literal },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Case-insensitive substring match.
+    Contains,
+    NotContains,
+}
+
+#[derive(Debug, Clone)]
+pub enum Literal {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Time(DateTime<Utc>),
+}
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    now: DateTime<Utc>,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>, now: DateTime<Utc>) -> Self {
+        Self { tokens, index: 0, now }
+    }
+
+    pub fn parse(mut self) -> Result<Expr, ParseError> {
+        let expr = self.or()?;
+        match self.peek() {
+            TokenKind::End => Ok(expr),
+            TokenKind::RParen => Err(self.error("unmatched `)`")),
+            _ => Err(self.error(format!("expected `and`, `or` or end of expression, found {}", self.peek().describe()))),
+        }
+    }
+
+    /// Parse the tokens as a single field path (see `path`).
+    pub fn parse_path(mut self) -> Result<String, ParseError> {
+        let pointer = self.path()?;
+        match self.peek() {
+            TokenKind::End => Ok(pointer),
+            found => Err(self.error(format!("expected end of field, found {}", found.describe()))),
+        }
+    }
+
+    fn peek(&self) -> &TokenKind {
+        &self.tokens[self.index].kind
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.index].clone();
+        if token.kind != TokenKind::End {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError::new(message, self.tokens[self.index].position)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            TokenKind::Ident(name) if name.eq_ignore_ascii_case(keyword) => {
+                self.advance();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.not()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, ParseError> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if *self.peek() == TokenKind::LParen {
+            let open = self.advance();
+            let expr = self.or()?;
+            if *self.peek() != TokenKind::RParen {
+                return Err(ParseError::new("unclosed `(`", open.position));
+            }
+            self.advance();
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
+        let pointer = self.path()?;
+
+        let op = match self.peek() {
+            TokenKind::Eq => Op::Eq,
+            TokenKind::Ne => Op::Ne,
+            TokenKind::Lt => Op::Lt,
+            TokenKind::Le => Op::Le,
+            TokenKind::Gt => Op::Gt,
+            TokenKind::Ge => Op::Ge,
+            TokenKind::Contains => Op::Contains,
+            TokenKind::NotContains => Op::NotContains,
+            found => {
+                return Err(self.error(format!(
+                    "expected an operator (==, !=, <, <=, >, >=, ~, !~), found {}",
+                    found.describe()
+                )))
+            }
+        };
+        self.advance();
+
+        let position = self.tokens[self.index].position;
+        let value = self.value()?;
+        match (op, &value) {
+            (Op::Contains | Op::NotContains, Literal::String(_)) => {}
+            (Op::Contains | Op::NotContains, _) => {
+                return Err(ParseError::new("`~` and `!~` need a string", position))
+            }
+            (Op::Lt | Op::Le | Op::Gt | Op::Ge, Literal::Null | Literal::Bool(_)) => {
+                return Err(ParseError::new("cannot order by null or a boolean", position))
+            }
+            _ => {}
+        }
+        Ok(Expr::Compare { pointer, op, value })
+    }
+
+    /// A field such as `retries`, `headers.traceparent` or
+    /// `payload.items[0]["user id"]`, as a JSON Pointer into the document.
+    fn path(&mut self) -> Result<String, ParseError> {
+        let field = match self.peek() {
+            TokenKind::Ident(name) if !["and", "or", "not"].contains(&name.to_lowercase().as_str()) => name.clone(),
+            found => return Err(self.error(format!("expected a field, found {}", found.describe()))),
+        };
+        if !DOCUMENT_FIELDS.contains(&field.as_str()) {
+            return Err(self.error(format!(
+                "unknown field `{}`; expected one of {}",
+                field,
+                DOCUMENT_FIELDS.join(", ")
+            )));
+        }
+        self.advance();
+
+        let mut pointer = format!("/{}", field);
+        loop {
+            let segment = match self.peek() {
+                TokenKind::Dot => {
+                    self.advance();
+                    let TokenKind::Ident(name) = self.peek().clone() else {
+                        return Err(self.error("expected a field name after `.`"));
+                    };
+                    self.advance();
+                    name
+                }
+                TokenKind::LBracket => {
+                    self.advance();
+                    let segment = match self.peek().clone() {
+                        TokenKind::String(name) => name,
+                        TokenKind::Number(index) if index.fract() == 0.0 && index >= 0.0 => {
+                            (index as u64).to_string()
+                        }
+                        _ => return Err(self.error("expected an index or a quoted field name")),
+                    };
+                    self.advance();
+                    if *self.peek() != TokenKind::RBracket {
+                        return Err(self.error("expected `]`"));
+                    }
+                    self.advance();
+                    segment
+                }
+                _ => return Ok(pointer),
+            };
+            pointer.push('/');
+            pointer.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+        }
+    }
+
+    fn value(&mut self) -> Result<Literal, ParseError> {
+        let token = self.advance();
+        match token.kind {
+            TokenKind::String(text) => Ok(Literal::String(text)),
+            TokenKind::Number(number) => Ok(Literal::Number(number)),
+            TokenKind::Minus => match self.advance().kind {
+                TokenKind::Number(number) => Ok(Literal::Number(-number)),
+                _ => Err(ParseError::new("expected a number after `-`", token.position)),
+            },
+            TokenKind::Duration(_) => Err(ParseError::new(
+                "durations are relative to now, e.g. `now-2d`",
+                token.position,
+            )),
+            TokenKind::Ident(name) => match name.as_str() {
+                "null" => Ok(Literal::Null),
+                "true" => Ok(Literal::Bool(true)),
+                "false" => Ok(Literal::Bool(false)),
+                "now" => {
+                    let sign = match self.peek() {
+                        TokenKind::Plus => 1,
+                        TokenKind::Minus => -1,
+                        _ => return Ok(Literal::Time(self.now)),
+                    };
+                    self.advance();
+                    match self.peek() {
+                        TokenKind::Duration(seconds) => {
+                            let time = TimeDelta::try_seconds(sign * seconds)
+                                .and_then(|delta| self.now.checked_add_signed(delta))
+                                .ok_or_else(|| self.error("duration is out of range"))?;
+                            self.advance();
+                            Ok(Literal::Time(time))
+                        }
+                        _ => Err(self.error("expected a duration such as `2d`, `6h` or `30m`")),
+                    }
+                }
+                _ => Err(ParseError::new(
+                    format!("expected a value, found `{}` (strings need double quotes)", name),
+                    token.position,
+                )),
+            },
+            found => Err(ParseError::new(
+                format!("expected a value, found {}", found.describe()),
+                token.position,
+            )),
+        }
+    }
+}
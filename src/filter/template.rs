@@ -0,0 +1,83 @@
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::filter::{Field, ParseError};
+
+/// Text with `{field}` placeholders, e.g. `{id} failed: {reason}`, rendered
+/// once per message. `{{` and `}}` stand for literal braces; missing fields
+/// render as nothing.
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut rest = text.char_indices().peekable();
+
+        while let Some((position, c)) = rest.next() {
+            match c {
+                '{' if rest.peek().is_some_and(|(_, next)| *next == '{') => {
+                    rest.next();
+                    literal.push('{');
+                }
+                '}' if rest.peek().is_some_and(|(_, next)| *next == '}') => {
+                    rest.next();
+                    literal.push('}');
+                }
+                '}' => return Err(ParseError::new("unmatched `}` (write `}}` for a brace)", position)),
+                '{' => {
+                    let start = position + 1;
+                    let end = text[start..]
+                        .find('}')
+                        .map(|len| start + len)
+                        .ok_or_else(|| ParseError::new("unclosed `{`", position))?;
+                    let field = Field::parse(text[start..end].trim()).map_err(|e| {
+                        // Point into the template rather than the placeholder
+                        let leading = text[start..end].len() - text[start..end].trim_start().len();
+                        ParseError::new(e.message, start + leading + e.position)
+                    })?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field));
+                    while rest.peek().is_some_and(|(i, _)| *i <= end) {
+                        rest.next();
+                    }
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    pub fn render(&self, document: &Value) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Field(field) => field.text(document).unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Template::parse(text).map_err(|e| e.render(text))
+    }
+}
@@ -0,0 +1,96 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::anyhow;
+use rdkafka::{error::KafkaError, ClientConfig};
+
+use crate::{
+    kafka::{
+        BOOTSTRAP_SERVERS, MESSAGE_TIMEOUT_MS, SASL_MECHANISM, SASL_PASSWORD, SASL_USERNAME,
+        SECURITY_PROTOCOL, SESSION_TIMEOUT_MS, SSL_CA_LOCATION, SSL_CERTIFICATE_LOCATION,
+        SSL_KEY_LOCATION, SSL_KEY_PASSWORD, TIMEOUT_MS,
+    },
+    settings::Settings,
+};
+
+/// Consumer configuration: the base client config, the tool's defaults and
+/// then the user's extra consumer properties.
+pub fn consumer_config(settings: &Settings) -> ClientConfig {
+    let mut config = client_config(settings);
+    config.set(SESSION_TIMEOUT_MS, TIMEOUT_MS);
+    for (key, value) in &settings.consumer_properties {
+        config.set(key, value);
+    }
+    config
+}
+
+/// Producer configuration: the base client config, the tool's defaults and
+/// then the user's extra producer properties.
+pub fn producer_config(settings: &Settings) -> ClientConfig {
+    let mut config = client_config(settings);
+    config.set(MESSAGE_TIMEOUT_MS, TIMEOUT_MS);
+    for (key, value) in &settings.producer_properties {
+        config.set(key, value);
+    }
+    config
+}
+
+/// Base configuration for every Kafka client: brokers plus the profile's
+/// security settings. Never print the result, it holds credentials.
+pub fn client_config(settings: &Settings) -> ClientConfig {
+    let security = &settings.security;
+    let mut config = ClientConfig::new();
+    config.set(BOOTSTRAP_SERVERS, &settings.brokers);
+
+    if let Some(protocol) = &security.protocol {
+        config.set(SECURITY_PROTOCOL, protocol);
+    }
+    if let Some(mechanism) = &security.sasl_mechanism {
+        config.set(SASL_MECHANISM, mechanism);
+    }
+    if let Some(username) = &security.sasl_username {
+        config.set(SASL_USERNAME, username);
+    }
+    if let Some(password) = &security.sasl_password {
+        config.set(SASL_PASSWORD, password.expose());
+    }
+    set_path(&mut config, SSL_CA_LOCATION, security.ssl_ca_location.as_deref());
+    set_path(&mut config, SSL_CERTIFICATE_LOCATION, security.ssl_certificate_location.as_deref());
+    set_path(&mut config, SSL_KEY_LOCATION, security.ssl_key_location.as_deref());
+    if let Some(password) = &security.ssl_key_password {
+        config.set(SSL_KEY_PASSWORD, password.expose());
+    }
+
+    config
+}
+
+fn set_path(config: &mut ClientConfig, key: &str, path: Option<&Path>) {
+    if let Some(path) = path {
+        config.set(key, path.to_string_lossy());
+    }
+}
+
+/// Check extra properties against librdkafka without creating a client, so
+/// that unknown keys and invalid values are reported up front.
+pub fn validate_properties(
+    client: &str,
+    properties: &BTreeMap<String, String>,
+) -> Result<(), anyhow::Error> {
+    for (key, value) in properties {
+        let mut config = ClientConfig::new();
+        config.set(key, value);
+
+        if let Err(e) = config.create_native_config() {
+            return Err(match e {
+                KafkaError::ClientConfig(_, description, _, _) => anyhow!(
+                    "Invalid {} property {}={}: {}",
+                    client,
+                    key,
+                    value,
+                    description
+                ),
+                e => anyhow!("Invalid {} property {}={}: {}", client, key, value, e),
+            });
+        }
+    }
+    Ok(())
+}
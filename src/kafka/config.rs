@@ -6,6 +6,14 @@ pub const AUTO_OFFSET_RESET: &str = "auto.offset.reset";
 pub const ENABLE_AUTO_COMMIT: &str = "enable.auto.commit";
 pub const SESSION_TIMEOUT_MS: &str = "session.timeout.ms";
 pub const MESSAGE_TIMEOUT_MS: &str = "message.timeout.ms";
+pub const SECURITY_PROTOCOL: &str = "security.protocol";
+pub const SASL_MECHANISM: &str = "sasl.mechanism";
+pub const SASL_USERNAME: &str = "sasl.username";
+pub const SASL_PASSWORD: &str = "sasl.password";
+pub const SSL_CA_LOCATION: &str = "ssl.ca.location";
+pub const SSL_CERTIFICATE_LOCATION: &str = "ssl.certificate.location";
+pub const SSL_KEY_LOCATION: &str = "ssl.key.location";
+pub const SSL_KEY_PASSWORD: &str = "ssl.key.password";
 
 // Timeout value (in milliseconds)
 pub const TIMEOUT_MS: &str = "6000";  // 6 seconds
@@ -13,4 +21,9 @@ pub const TIMEOUT_MS: &str = "6000";  // 6 seconds
 // Kafka message headers
 pub const MESSAGE_HEADER_MESSAGE_ID: &str = "id";
 pub const MESSAGE_HEADER_CORRELATION_ID: &str = "correlation_id";
-pub const MESSAGE_HEADER_REPUBLISHED_AT: &str = "republished_at";
\ No newline at end of file
+pub const MESSAGE_HEADER_REPUBLISHED_AT: &str = "republished_at";
+pub const MESSAGE_HEADER_ARCHIVED_AT: &str = "archived_at";
+pub const MESSAGE_HEADER_ORIGINAL_TIMESTAMP: &str = "original_timestamp";
+pub const MESSAGE_HEADER_SOURCE_TOPIC: &str = "x-dlq-source-topic";
+pub const MESSAGE_HEADER_SOURCE_PARTITION: &str = "x-dlq-source-partition";
+pub const MESSAGE_HEADER_SOURCE_OFFSET: &str = "x-dlq-source-offset";
\ No newline at end of file
@@ -1,5 +1,11 @@
+pub mod client;
 pub mod context;
 pub mod config;
+pub mod producer;
+pub mod scanner;
 
+pub use client::*;
 pub use context::*;
-pub use config::*;
\ No newline at end of file
+pub use config::*;
+pub use producer::*;
+pub use scanner::*;
\ No newline at end of file
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use rdkafka::{
+    message::{Header, OwnedHeaders},
+    producer::{DeliveryFuture, FutureProducer, FutureRecord},
+};
+
+use crate::{envelope::OutgoingRecord, kafka::RecordHeader};
+
+/// Produce `record` to `topic` and wait for the delivery report.
+pub async fn send_record(
+    producer: &FutureProducer,
+    topic: &str,
+    record: &OutgoingRecord,
+) -> Result<(), anyhow::Error> {
+    let mut future_record = FutureRecord::to(topic)
+        .payload(&record.payload)
+        .headers(owned_headers(&record.headers));
+    if let Some(key) = &record.key {
+        future_record = future_record.key(key);
+    }
+
+    producer
+        .send(future_record, Duration::from_secs(0))
+        .await
+        .map_err(|(err, _)| anyhow!("Failed to produce to {}: {}", topic, err))?;
+    Ok(())
+}
+
+/// Queue `record` for `topic` without waiting, so that several records can
+/// be in flight at once. Await the result with `delivered`.
+pub fn queue_record(
+    producer: &FutureProducer,
+    topic: &str,
+    record: &OutgoingRecord,
+) -> Result<DeliveryFuture, anyhow::Error> {
+    let mut future_record = FutureRecord::to(topic)
+        .payload(&record.payload)
+        .headers(owned_headers(&record.headers));
+    if let Some(key) = &record.key {
+        future_record = future_record.key(key);
+    }
+
+    producer
+        .send_result(future_record)
+        .map_err(|(err, _)| anyhow!("Failed to produce to {}: {}", topic, err))
+}
+
+/// Wait for the delivery report of a record queued with `queue_record`.
+pub async fn delivered(delivery: DeliveryFuture, topic: &str) -> Result<(), anyhow::Error> {
+    match delivery.await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err((err, _))) => Err(anyhow!("Failed to produce to {}: {}", topic, err)),
+        Err(_) => Err(anyhow!("Producer closed before delivering to {}", topic)),
+    }
+}
+
+pub fn owned_headers(headers: &[RecordHeader]) -> OwnedHeaders {
+    headers.iter().fold(OwnedHeaders::new(), |owned, header| {
+        owned.insert(Header {
+            key: &header.key,
+            value: header.value.as_deref(),
+        })
+    })
+}
@@ -0,0 +1,776 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt,
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use rdkafka::{
+    config::RDKafkaLogLevel,
+    consumer::{CommitMode, Consumer},
+    error::KafkaError,
+    message::{BorrowedMessage, Headers},
+    Message, Offset, TopicPartitionList,
+};
+
+use crate::{
+    cli::{DlqMessage, EnvelopeMapping},
+    envelope::{self, cloudevents, EnvelopeFormat},
+    schema::{wire_schema_id, AvroCodec, PayloadEncoding, ProtobufCodec},
+    kafka::{
+        consumer_config, CustomContext, LoggingConsumer, AUTO_OFFSET_RESET, ENABLE_AUTO_COMMIT,
+        ENABLE_PARTITION_EOF, GROUP_ID,
+    },
+    settings::Settings,
+};
+
+/// How the scanner attaches to the DLQ topic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanMode {
+    /// Read every partition from its low watermark using manual assignment.
+    /// No consumer group is joined and nothing is committed.
+    Browse,
+    /// Like `Browse`, but start each partition at the consumer group's
+    /// committed offset so only unhandled messages are shown.
+    FromCommitted,
+    /// Join the consumer group so that handled records can be committed.
+    /// Used by commands that change the DLQ.
+    Group,
+}
+
+/// What to do with a record that cannot be decoded, e.g. because its payload
+/// is missing or is not a JSON envelope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseFailurePolicy {
+    /// Log a warning and move on to the next record.
+    Skip,
+    /// Return the record undecoded, with `parse_error` set.
+    Keep,
+}
+
+/// A Kafka header copied out of a DLQ record.
+#[derive(Debug, Clone)]
+pub struct RecordHeader {
+    pub key: String,
+    pub value: Option<Vec<u8>>,
+}
+
+/// A DLQ record together with its Kafka metadata and decoded envelope.
+#[derive(Debug, Clone)]
+pub struct DlqRecord {
+    pub topic: String,
+    pub partition: i32,
+    pub offset: i64,
+    pub key: Option<Vec<u8>>,
+    pub headers: Vec<RecordHeader>,
+    /// Record timestamp in milliseconds since the epoch, if the broker set one.
+    pub timestamp: Option<i64>,
+    pub payload: Option<Vec<u8>>,
+    /// The DLQ convention the record was decoded with.
+    pub format: EnvelopeFormat,
+    /// The JSON body exactly as it was read from the topic, or `Null` when
+    /// the body is not JSON. Avro and protobuf payloads are stored here in
+    /// decoded form.
+    pub envelope: serde_json::Value,
+    /// Set when the payload was decoded from Avro or protobuf
+    pub payload_encoding: Option<PayloadEncoding>,
+    /// Why the record could not be decoded, for records kept with
+    /// `ParseFailurePolicy::Keep`
+    pub parse_error: Option<String>,
+    pub message: DlqMessage,
+}
+
+impl DlqRecord {
+    /// Value of the last header named `key`.
+    pub fn header(&self, key: &str) -> Option<&[u8]> {
+        self.headers
+            .iter()
+            .rev()
+            .find(|header| header.key == key)
+            .and_then(|header| header.value.as_deref())
+    }
+
+    /// The record key as text, if it has one.
+    pub fn key_text(&self) -> Option<String> {
+        self.key.as_deref().map(|key| String::from_utf8_lossy(key).into_owned())
+    }
+}
+
+// Timeout for metadata and watermark requests
+const METADATA_TIMEOUT: Duration = Duration::from_secs(10);
+// Records inspected to detect the envelope format of a topic
+const DETECTION_SAMPLE_SIZE: usize = 20;
+
+/// Partitions and records covered by a scan so far.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanSummary {
+    pub partitions: usize,
+    pub messages_read: usize,
+    pub unparseable: usize,
+}
+
+impl fmt::Display for ScanSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "scanned {} partition(s), read {} message(s)",
+            self.partitions, self.messages_read
+        )?;
+        if self.unparseable > 0 {
+            write!(f, ", {} unparseable", self.unparseable)?;
+        }
+        Ok(())
+    }
+}
+
+/// Record timestamps a scan is limited to: from `since` (inclusive) to
+/// `until` (exclusive). Unset bounds are open.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeRange {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    pub fn is_unbounded(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    /// Whether a record with this timestamp (epoch millis) is in range.
+    /// Records without a timestamp only match an unbounded range.
+    pub fn contains(&self, timestamp: Option<i64>) -> bool {
+        if self.is_unbounded() {
+            return true;
+        }
+        let Some(timestamp) = timestamp else {
+            return false;
+        };
+        self.since.is_none_or(|since| timestamp >= since.timestamp_millis())
+            && self.until.is_none_or(|until| timestamp < until.timestamp_millis())
+    }
+}
+
+/// Where a read-only scan stopped: the next offset to read per partition.
+/// Written as `PARTITION:OFFSET,...`, e.g. `0:120,1:57`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cursor(BTreeMap<i32, i64>);
+
+impl Cursor {
+    pub fn offset(&self, partition: i32) -> Option<i64> {
+        self.0.get(&partition).copied()
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let positions: Vec<String> = self
+            .0
+            .iter()
+            .map(|(partition, offset)| format!("{}:{}", partition, offset))
+            .collect();
+        f.write_str(&positions.join(","))
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        text.split(',')
+            .map(|position| {
+                position
+                    .trim()
+                    .split_once(':')
+                    .and_then(|(partition, offset)| Some((partition.parse().ok()?, offset.parse().ok()?)))
+                    .ok_or_else(|| format!("expected PARTITION:OFFSET,..., got `{}`", text))
+            })
+            .collect::<Result<_, _>>()
+            .map(Cursor)
+    }
+}
+
+/// Reads a DLQ topic and yields parsed records.
+///
+/// All commands go through the scanner so that they agree on how records
+/// are parsed, how Kafka errors are reported (always returned as errors) and
+/// when a scan is finished: once every partition has reached the high
+/// watermark it had when the scan started.
+pub struct DlqScanner {
+    consumer: LoggingConsumer,
+    topic: String,
+    mode: ScanMode,
+    mapping: EnvelopeMapping,
+    /// Pinned in the config, or detected from the first records
+    format: Option<EnvelopeFormat>,
+    /// Records read for detection that have not been returned yet
+    sample: VecDeque<DlqRecord>,
+    /// Decodes Avro payloads when a schema source is configured
+    avro: Option<AvroCodec>,
+    /// Decodes protobuf payloads when a message type is configured
+    protobuf: Option<ProtobufCodec>,
+    on_parse_failure: ParseFailurePolicy,
+    /// Partitions that have not been read to the end yet
+    pending: HashSet<i32>,
+    /// High watermark per partition as of scan start, or the first offset
+    /// past the time range
+    end_offsets: HashMap<i32, i64>,
+    range: TimeRange,
+    /// Next offset to read per partition, for `cursor`
+    positions: HashMap<i32, i64>,
+    /// First offset per partition that was passed over: read but outside the
+    /// time range, or before the offset a group scan sought to
+    skipped: HashMap<i32, i64>,
+    /// The group's committed offset per partition as of scan start
+    committed: HashMap<i32, i64>,
+    summary: ScanSummary,
+}
+
+impl DlqScanner {
+    pub fn new(settings: &Settings, topic: &str, mode: ScanMode) -> Result<Self, anyhow::Error> {
+        Self::open(settings, topic, mode, None, TimeRange::default(), None)
+    }
+
+    /// A scanner that only reads records with timestamps in `range`. Each
+    /// partition starts at the first offset at or after `since` and stops
+    /// at the first offset at or after `until`, as resolved by the broker.
+    pub fn in_range(
+        settings: &Settings,
+        topic: &str,
+        mode: ScanMode,
+        range: TimeRange,
+    ) -> Result<Self, anyhow::Error> {
+        Self::open(settings, topic, mode, None, range, None)
+    }
+
+    /// Like `in_range`, but continue a read-only scan where `cursor` says an
+    /// earlier one stopped.
+    pub fn resume(
+        settings: &Settings,
+        topic: &str,
+        mode: ScanMode,
+        range: TimeRange,
+        cursor: &Cursor,
+    ) -> Result<Self, anyhow::Error> {
+        if mode == ScanMode::Group {
+            return Err(anyhow!("A cursor can only resume a read-only scan"));
+        }
+        Self::open(settings, topic, mode, None, range, Some(cursor))
+    }
+
+    /// A scanner that seeks straight to `offset` in `partition` and reads
+    /// only that record.
+    pub fn at(
+        settings: &Settings,
+        topic: &str,
+        mode: ScanMode,
+        partition: i32,
+        offset: i64,
+    ) -> Result<Self, anyhow::Error> {
+        Self::open(settings, topic, mode, Some((partition, offset)), TimeRange::default(), None)
+    }
+
+    fn open(
+        settings: &Settings,
+        topic: &str,
+        mode: ScanMode,
+        position: Option<(i32, i64)>,
+        range: TimeRange,
+        cursor: Option<&Cursor>,
+    ) -> Result<Self, anyhow::Error> {
+        let mut config = consumer_config(settings);
+        config
+            .set(ENABLE_PARTITION_EOF, "true")  // Enable EOF detection
+            .set(AUTO_OFFSET_RESET, "earliest") // Start from beginning
+            .set(ENABLE_AUTO_COMMIT, "false")   // Don't auto-commit
+            .set_log_level(RDKafkaLogLevel::Debug);
+        // Browsing needs no group; reading committed offsets needs the group id
+        // but never joins it
+        if mode != ScanMode::Browse {
+            config.set(GROUP_ID, &settings.group_id);
+        }
+
+        let consumer: LoggingConsumer = config
+            .create_with_context(CustomContext)
+            .context("Consumer creation failed")?;
+
+        let watermarks = fetch_watermarks(&consumer, topic)?;
+        let mut end_offsets: HashMap<i32, i64> = watermarks
+            .iter()
+            .map(|(partition, (_, high))| (*partition, *high))
+            .collect();
+        if let Some(until) = range.until {
+            for (partition, offset) in fetch_offsets_for_time(&consumer, topic, &watermarks, until)? {
+                if let (Some(offset), Some(end)) = (offset, end_offsets.get_mut(&partition)) {
+                    *end = (*end).min(offset);
+                }
+            }
+        }
+
+        let committed = match mode {
+            ScanMode::Group => fetch_committed_offsets(&consumer, topic, &watermarks)?,
+            _ => HashMap::new(),
+        };
+        let mut skipped = HashMap::new();
+
+        let (pending, positions) = match (position, mode) {
+            (Some((partition, offset)), _) => {
+                let (low, high) = *watermarks
+                    .get(&partition)
+                    .ok_or_else(|| anyhow!("Partition {} not found in {}", partition, topic))?;
+                if offset < low || offset >= high {
+                    return Err(anyhow!(
+                        "Offset {} is out of range for {}/{} (available: {}..{})",
+                        offset,
+                        topic,
+                        partition,
+                        low,
+                        high
+                    ));
+                }
+
+                let mut assignment = TopicPartitionList::new();
+                assignment
+                    .add_partition_offset(topic, partition, Offset::Offset(offset))
+                    .context("Invalid start offset")?;
+                consumer
+                    .assign(&assignment)
+                    .map_err(|e| anyhow!("Failed to assign partition: {}", e))?;
+
+                // Stop right after the requested record
+                end_offsets = HashMap::from([(partition, offset + 1)]);
+                // Unhandled records before it are passed over
+                if let Some(first) = committed.get(&partition).filter(|first| **first < offset) {
+                    skipped.insert(partition, *first);
+                }
+                (HashSet::from([partition]), HashMap::from([(partition, offset)]))
+            }
+            (None, ScanMode::Group) => {
+                consumer
+                    .subscribe(&[topic])
+                    .map_err(|e| anyhow!("Failed to subscribe to topic: {}", e))?;
+                // Start positions come from the group; partitions that are
+                // already consumed report EOF straight away
+                // Records before `since` are not sought past but skipped while
+                // reading, and reported by `skipped` so that they are not
+                // committed as handled
+                let pending = watermarks
+                    .iter()
+                    .filter(|(partition, (low, _))| end_offsets[partition] > *low)
+                    .map(|(partition, _)| *partition)
+                    .collect();
+                (pending, HashMap::new())
+            }
+            (None, ScanMode::Browse | ScanMode::FromCommitted) => {
+                let mut start_offsets = match mode {
+                    ScanMode::FromCommitted => {
+                        fetch_committed_offsets(&consumer, topic, &watermarks)?
+                    }
+                    _ => watermarks
+                        .iter()
+                        .map(|(partition, (low, _))| (*partition, *low))
+                        .collect(),
+                };
+                if let Some(since) = range.since {
+                    for (partition, offset) in fetch_offsets_for_time(&consumer, topic, &watermarks, since)? {
+                        if let Some(start) = start_offsets.get_mut(&partition) {
+                            // No record that new means nothing to read
+                            *start = offset.map_or(end_offsets[&partition], |offset| (*start).max(offset));
+                        }
+                    }
+                }
+
+                if let Some(cursor) = cursor {
+                    for (partition, start) in start_offsets.iter_mut() {
+                        if let Some(offset) = cursor.offset(*partition) {
+                            *start = (*start).max(offset);
+                        }
+                    }
+                }
+
+                let mut assignment = TopicPartitionList::new();
+                for (partition, offset) in &start_offsets {
+                    assignment
+                        .add_partition_offset(topic, *partition, Offset::Offset(*offset))
+                        .context("Invalid start offset")?;
+                }
+                consumer
+                    .assign(&assignment)
+                    .map_err(|e| anyhow!("Failed to assign partitions: {}", e))?;
+
+                let pending = start_offsets
+                    .iter()
+                    .filter(|(partition, start)| end_offsets[partition] > **start)
+                    .map(|(partition, _)| *partition)
+                    .collect();
+                (pending, start_offsets)
+            }
+        };
+
+        Ok(Self {
+            consumer,
+            topic: topic.to_string(),
+            mode,
+            mapping: settings.envelope_mapping(topic),
+            format: settings.envelope_format(topic),
+            sample: VecDeque::new(),
+            avro: AvroCodec::from_settings(settings),
+            protobuf: ProtobufCodec::from_settings(settings, topic)?,
+            on_parse_failure: ParseFailurePolicy::Skip,
+            pending,
+            range,
+            positions,
+            skipped,
+            committed,
+            summary: ScanSummary {
+                partitions: end_offsets.len(),
+                ..Default::default()
+            },
+            end_offsets,
+        })
+    }
+
+    pub fn summary(&self) -> ScanSummary {
+        self.summary
+    }
+
+    /// Per partition, the first record that was passed over: read but outside
+    /// the time range, or before the offset a group scan sought to.
+    /// Committing past it would mark it handled.
+    pub fn skipped(&self) -> &HashMap<i32, i64> {
+        &self.skipped
+    }
+
+    /// The group's committed offset per partition as of scan start; empty
+    /// for read-only scans.
+    pub fn committed(&self) -> &HashMap<i32, i64> {
+        &self.committed
+    }
+
+    /// Where fields of the decoded records come from.
+    pub fn mapping(&self) -> &EnvelopeMapping {
+        &self.mapping
+    }
+
+    pub fn on_parse_failure(mut self, policy: ParseFailurePolicy) -> Self {
+        self.on_parse_failure = policy;
+        self
+    }
+
+    /// The envelope format records are decoded with. Unless it is pinned in
+    /// the config, the first records are read to detect it; `None` means the
+    /// topic has nothing to detect it from.
+    pub async fn format(&mut self) -> Result<Option<EnvelopeFormat>, anyhow::Error> {
+        if self.format.is_none() {
+            while self.sample.len() < DETECTION_SAMPLE_SIZE {
+                match self.read().await? {
+                    Some(record) => self.sample.push_back(record),
+                    None => break,
+                }
+            }
+            self.sample.make_contiguous();
+            self.format = envelope::detect(self.sample.as_slices().0, &self.mapping);
+            if let Some(format) = self.format {
+                info!("Detected {} envelope format for {}", format, self.topic);
+            }
+        }
+        Ok(self.format)
+    }
+
+    /// Next parsed record, or `None` once the end of the topic is reached.
+    pub async fn next(&mut self) -> Result<Option<DlqRecord>, anyhow::Error> {
+        loop {
+            let Some(format) = self.format().await? else {
+                return Ok(None);
+            };
+            let mut record = match self.sample.pop_front() {
+                Some(record) => record,
+                None => match self.read().await? {
+                    Some(record) => record,
+                    None => return Ok(None),
+                },
+            };
+
+            match envelope::decode(&mut record, format, &self.mapping) {
+                Ok(()) => {
+                    self.decode_payload(&mut record).await;
+                    return Ok(Some(record));
+                }
+                Err(reason) => {
+                    self.summary.unparseable += 1;
+                    match self.on_parse_failure {
+                        ParseFailurePolicy::Skip => warn!(
+                            "Skipping record at partition {} offset {}: {}",
+                            record.partition, record.offset, reason
+                        ),
+                        ParseFailurePolicy::Keep => {
+                            record.parse_error = Some(reason);
+                            return Ok(Some(record));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replace an Avro payload in the Confluent wire format, or a protobuf
+    /// payload of the configured type, with its JSON form. Payloads that
+    /// cannot be decoded are left as they are.
+    async fn decode_payload(&mut self, record: &mut DlqRecord) {
+        // JSON envelopes and structured CloudEvents are never Avro or protobuf;
+        // other formats carry the original value
+        let structured = record.format == EnvelopeFormat::CloudEvents
+            && !cloudevents::is_binary(record);
+        if record.format == EnvelopeFormat::Json || structured {
+            return;
+        }
+        let Some(payload) = record.payload.as_deref() else {
+            return;
+        };
+
+        let decoded = match (&mut self.avro, &self.protobuf) {
+            (Some(codec), _) if wire_schema_id(payload).is_some() => codec
+                .decode(payload)
+                .await
+                .map(|(schema_id, json)| (PayloadEncoding::Avro { schema_id }, json)),
+            (_, Some(codec)) => codec
+                .decode(payload)
+                .map(|json| (PayloadEncoding::Protobuf, json)),
+            _ => return,
+        };
+
+        match decoded {
+            Ok((encoding, json)) => {
+                record.message.payload =
+                    serde_json::to_string_pretty(&json).unwrap_or_else(|_| "-".to_string());
+                record.envelope = json;
+                record.payload_encoding = Some(encoding);
+            }
+            Err(e) => warn!(
+                "Could not decode payload at partition {} offset {}: {:#}",
+                record.partition, record.offset, e
+            ),
+        }
+    }
+
+    /// Next record from the topic, not decoded yet.
+    async fn read(&mut self) -> Result<Option<DlqRecord>, anyhow::Error> {
+        loop {
+            if self.pending.is_empty() {
+                info!("Reached end of all partitions of {}", self.topic);
+                return Ok(None);
+            }
+
+            let m = match self.consumer.recv().await {
+                Ok(m) => m,
+                Err(KafkaError::PartitionEOF(partition)) => {
+                    info!("Reached end of partition {} (EOF)", partition);
+                    self.pending.remove(&partition);
+                    continue;
+                }
+                Err(e) => return Err(anyhow!("Kafka error while reading {}: {}", self.topic, e)),
+            };
+
+            self.positions.insert(m.partition(), m.offset() + 1);
+
+            // Ignore records produced after the scan started
+            let end = self.end_offsets.get(&m.partition()).copied().unwrap_or(0);
+            if m.offset() >= end {
+                self.pending.remove(&m.partition());
+                continue;
+            }
+            if m.offset() + 1 >= end {
+                self.pending.remove(&m.partition());
+            }
+            // Timestamps are not ordered within a partition, so the offsets
+            // from the broker only narrow the scan
+            if !self.range.contains(m.timestamp().to_millis()) {
+                self.skipped.entry(m.partition()).or_insert(m.offset());
+                continue;
+            }
+            self.summary.messages_read += 1;
+
+            return Ok(Some(to_record(&m)));
+        }
+    }
+
+    /// Where to resume this scan, or `None` once every partition has been
+    /// read to its end. Records read ahead for format detection count as
+    /// unread.
+    pub fn cursor(&self) -> Option<Cursor> {
+        if self.pending.is_empty() && self.sample.is_empty() {
+            return None;
+        }
+        let mut positions: BTreeMap<i32, i64> = self
+            .positions
+            .iter()
+            .map(|(partition, offset)| (*partition, (*offset).min(self.end_offsets[partition])))
+            .collect();
+        for record in &self.sample {
+            let offset = positions.entry(record.partition).or_insert(record.offset);
+            *offset = (*offset).min(record.offset);
+        }
+        Some(Cursor(positions))
+    }
+
+    /// Commit the consumer group offset past `record`, marking it (and every
+    /// earlier record in its partition) as handled.
+    pub fn commit(&self, record: &DlqRecord) -> Result<(), anyhow::Error> {
+        if self.mode != ScanMode::Group {
+            return Err(anyhow!("Cannot commit offsets from a read-only scan"));
+        }
+
+        let mut offsets = TopicPartitionList::new();
+        offsets
+            .add_partition_offset(&record.topic, record.partition, Offset::Offset(record.offset + 1))
+            .context("Invalid offset to commit")?;
+
+        self.consumer
+            .commit(&offsets, CommitMode::Sync)
+            .context("Failed to commit DLQ offset")
+    }
+}
+
+/// Low and high watermark of every partition of `topic`.
+fn fetch_watermarks(
+    consumer: &LoggingConsumer,
+    topic: &str,
+) -> Result<HashMap<i32, (i64, i64)>, anyhow::Error> {
+    let metadata = consumer
+        .fetch_metadata(Some(topic), METADATA_TIMEOUT)
+        .map_err(|e| anyhow!("Failed to fetch metadata for {}: {}", topic, e))?;
+
+    let partitions = metadata
+        .topics()
+        .iter()
+        .find(|t| t.name() == topic)
+        .filter(|t| t.error().is_none())
+        .map(|t| t.partitions())
+        .ok_or_else(|| anyhow!("Topic not found: {}", topic))?;
+
+    let mut watermarks = HashMap::new();
+    for partition in partitions {
+        let (low, high) = consumer
+            .fetch_watermarks(topic, partition.id(), METADATA_TIMEOUT)
+            .map_err(|e| {
+                anyhow!("Failed to fetch watermarks for {}/{}: {}", topic, partition.id(), e)
+            })?;
+        watermarks.insert(partition.id(), (low, high));
+    }
+    Ok(watermarks)
+}
+
+/// The first offset whose timestamp is at or after `time` in every partition,
+/// or `None` where no record is that recent.
+fn fetch_offsets_for_time(
+    consumer: &LoggingConsumer,
+    topic: &str,
+    watermarks: &HashMap<i32, (i64, i64)>,
+    time: DateTime<Utc>,
+) -> Result<HashMap<i32, Option<i64>>, anyhow::Error> {
+    let mut timestamps = TopicPartitionList::new();
+    for partition in watermarks.keys() {
+        timestamps
+            .add_partition_offset(topic, *partition, Offset::Offset(time.timestamp_millis()))
+            .context("Invalid timestamp")?;
+    }
+
+    let offsets = consumer
+        .offsets_for_times(timestamps, METADATA_TIMEOUT)
+        .map_err(|e| anyhow!("Failed to look up offsets by time for {}: {}", topic, e))?;
+
+    Ok(watermarks
+        .keys()
+        .map(|partition| {
+            let offset = match offsets.find_partition(topic, *partition).map(|p| p.offset()) {
+                Some(Offset::Offset(offset)) => Some(offset),
+                _ => None,
+            };
+            (*partition, offset)
+        })
+        .collect())
+}
+
+/// The consumer group's committed offset for every partition, falling back to
+/// the low watermark where nothing was committed yet (or it was deleted).
+fn fetch_committed_offsets(
+    consumer: &LoggingConsumer,
+    topic: &str,
+    watermarks: &HashMap<i32, (i64, i64)>,
+) -> Result<HashMap<i32, i64>, anyhow::Error> {
+    let mut partitions = TopicPartitionList::new();
+    for partition in watermarks.keys() {
+        partitions.add_partition(topic, *partition);
+    }
+
+    let committed = consumer
+        .committed_offsets(partitions, METADATA_TIMEOUT)
+        .map_err(|e| anyhow!("Failed to fetch committed offsets for {}: {}", topic, e))?;
+
+    Ok(watermarks
+        .iter()
+        .map(|(partition, (low, _))| {
+            let offset = match committed.find_partition(topic, *partition).map(|p| p.offset()) {
+                Some(Offset::Offset(offset)) => offset.max(*low),
+                _ => *low,
+            };
+            (*partition, offset)
+        })
+        .collect())
+}
+
+/// Copy a Kafka message into a record that still has to be decoded.
+fn to_record(m: &BorrowedMessage) -> DlqRecord {
+    let headers = m
+        .headers()
+        .map(|headers| {
+            headers
+                .iter()
+                .map(|header| RecordHeader {
+                    key: header.key.to_string(),
+                    value: header.value.map(|v| v.to_vec()),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    DlqRecord {
+        topic: m.topic().to_string(),
+        partition: m.partition(),
+        offset: m.offset(),
+        key: m.key().map(|k| k.to_vec()),
+        headers,
+        timestamp: m.timestamp().to_millis(),
+        payload: m.payload().map(|p| p.to_vec()),
+        format: EnvelopeFormat::Json,
+        envelope: serde_json::Value::Null,
+        payload_encoding: None,
+        parse_error: None,
+        message: DlqMessage::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor: Cursor = " 1:57, 0:120 ".parse().unwrap();
+        assert_eq!(cursor.offset(0), Some(120));
+        assert_eq!(cursor.offset(1), Some(57));
+        assert_eq!(cursor.offset(2), None);
+        assert_eq!(cursor.to_string(), "0:120,1:57");
+        assert_eq!(cursor.to_string().parse::<Cursor>(), Ok(cursor));
+    }
+
+    #[test]
+    fn rejects_malformed_cursors() {
+        for text in ["", "0", "0:", ":5", "a:1", "0:x", "0:1,", "0:1;1:2", "0:1:2", "0:1.5"] {
+            let error = text.parse::<Cursor>().unwrap_err();
+            assert!(error.starts_with("expected PARTITION:OFFSET"), "{}: {}", text, error);
+        }
+    }
+}
@@ -1,70 +1,123 @@
 use clap::Parser;
 
 pub mod cli;
+pub mod envelope;
+pub mod filter;
 pub mod kafka;
+pub mod schema;
+pub mod settings;
 
 use cli::*;
+use settings::Settings;
 
 #[tokio::main]
 async fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
     let cli = cli::commands::Args::parse();
 
-    // TODO: Load from config.
-    let brokers = "localhost:9092";
-    let group_id = "poison_queue_cli_consumer_group_id";
-    let archive_topic = "dlq-archive";
+    let settings = match Settings::load(&cli.global) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Error loading configuration: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let output = cli.global.output;
+    if !output.is_table() {
+        colored::control::set_override(false);
+    }
 
     match cli.command {
         Some(Commands::ListTopics) => {
-            if let Err(e) = list_topics(brokers).await {
+            if let Err(e) = list_topics(&settings, output).await {
                 eprintln!("Error listing topics: {}", e);
                 std::process::exit(1);
             }
         },
         Some(Commands::ListMessages {
             topic,
+            from_committed,
+            filter,
+            page,
+            columns,
         }) => {
-            if let Err(e) = list_messages(brokers, group_id, &topic).await {
+            if let Err(e) = list_messages(&settings, &topic, from_committed, &filter.selector(), &page, &columns, output).await {
                 eprintln!("Error listing messages: {}", e);
                 std::process::exit(1);
             }
         },
         Some(Commands::ViewMessage {
-            topic,
-            message_id,
+            message,
+            from_committed,
+            hex,
         }) => {
-            if let Err(e) = view_message_by_id(brokers, group_id, &topic, &message_id).await {
+            let (topic, selector) = resolve_message(&message);
+            if let Err(e) = view_message_by_id(&settings, &topic, &selector, from_committed, hex, output).await {
                 eprintln!("Error listing messages: {}", e);
                 std::process::exit(1);
             }
         },
         Some(Commands::ArchiveMessage {
-            topic,
-            message_id,
+            message,
         }) => {
-            if let Err(e) = archive_message(brokers, group_id, &topic, &message_id, archive_topic).await {
+            let (topic, selector) = resolve_message(&message);
+            if let Err(e) = archive_message(&settings, &topic, &selector, output).await {
                 eprintln!("Error archiving message: {}", e);
                 std::process::exit(1);
             }
         },
-        Some(Commands::RepublishMessage {
+        Some(Commands::ArchiveMessages {
             topic,
-            message_id,
+            filter,
+            bulk,
+        }) => {
+            if let Err(e) = archive_messages(&settings, &topic, &filter.selector(), &bulk, output).await {
+                eprintln!("Error archiving messages: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Some(Commands::DiscardMessage {
+            message,
+        }) => {
+            let (topic, selector) = resolve_message(&message);
+            if let Err(e) = discard_message(&settings, &topic, &selector, output).await {
+                eprintln!("Error discarding message: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Some(Commands::RepublishMessage {
+            message,
             payload_file,
             dry_run,
+            target_topic,
         }) => {
+            let (topic, selector) = resolve_message(&message);
             if let Err(e) = republish_message(
-                brokers,
-                group_id,
+                &settings,
                 &topic,
-                &message_id,
+                &selector,
                 payload_file.as_deref(),
                 dry_run,
+                target_topic.as_deref(),
+                output,
             ).await {
                 eprintln!("Error republishing message: {}", e);
                 std::process::exit(1);
             }
         },
+        Some(Commands::RepublishMessages {
+            topic,
+            filter,
+            bulk,
+            republish,
+        }) => {
+            if let Err(e) = republish_messages(&settings, &topic, &filter.selector(), &bulk, &republish, output).await {
+                eprintln!("Error republishing messages: {}", e);
+                std::process::exit(1);
+            }
+        },
         None => {
             println!("Run with --help to see instructions");
             std::process::exit(0);
@@ -72,3 +125,11 @@ async fn main() {
     }
     
 }
+
+/// The DLQ topic and message selector given on the command line.
+fn resolve_message(message: &MessageArgs) -> (String, MessageSelector) {
+    message.resolve().unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    })
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context};
+use apache_avro::{
+    reader::datum::GenericDatumReader, types::Value as AvroValue, writer::datum::GenericDatumWriter,
+    Schema,
+};
+use serde_json::Value;
+
+use crate::{schema::SchemaSource, settings::Settings};
+
+// Confluent wire format: magic byte, 4 byte big-endian schema id, then the datum
+pub const WIRE_MAGIC_BYTE: u8 = 0;
+pub const WIRE_HEADER_LEN: usize = 5;
+
+/// Schema id of a payload in the Confluent wire format.
+pub fn wire_schema_id(payload: &[u8]) -> Option<u32> {
+    if payload.len() < WIRE_HEADER_LEN || payload[0] != WIRE_MAGIC_BYTE {
+        return None;
+    }
+    Some(u32::from_be_bytes(payload[1..WIRE_HEADER_LEN].try_into().ok()?))
+}
+
+/// Decodes and encodes Avro payloads in the Confluent wire format, caching
+/// every schema it looks up.
+pub struct AvroCodec {
+    source: SchemaSource,
+    schemas: HashMap<u32, Schema>,
+}
+
+impl AvroCodec {
+    pub fn new(source: SchemaSource) -> Self {
+        Self {
+            source,
+            schemas: HashMap::new(),
+        }
+    }
+
+    /// A codec for the configured schema source, if there is one.
+    pub fn from_settings(settings: &Settings) -> Option<Self> {
+        SchemaSource::from_settings(settings).map(Self::new)
+    }
+
+    async fn schema(&mut self, id: u32) -> Result<&Schema, anyhow::Error> {
+        if !self.schemas.contains_key(&id) {
+            let text = self.source.fetch(id).await?;
+            let schema = Schema::parse_str(&text)
+                .with_context(|| format!("Invalid Avro schema with id {}", id))?;
+            self.schemas.insert(id, schema);
+        }
+        Ok(&self.schemas[&id])
+    }
+
+    /// The schema id and the datum as JSON.
+    pub async fn decode(&mut self, payload: &[u8]) -> Result<(u32, Value), anyhow::Error> {
+        let id = wire_schema_id(payload)
+            .ok_or_else(|| anyhow!("payload is not in the Confluent wire format"))?;
+        let schema = self.schema(id).await?;
+
+        let mut datum = &payload[WIRE_HEADER_LEN..];
+        let value = GenericDatumReader::builder(schema)
+            .build()
+            .and_then(|reader| reader.read_value(&mut datum))
+            .with_context(|| format!("Failed to decode Avro payload with schema id {}", id))?;
+        let json = Value::try_from(value)
+            .with_context(|| format!("Avro payload with schema id {} has no JSON form", id))?;
+        Ok((id, json))
+    }
+
+    /// `json` encoded with schema `id`, including the wire format header.
+    pub async fn encode(&mut self, id: u32, json: &Value) -> Result<Vec<u8>, anyhow::Error> {
+        let schema = self.schema(id).await?;
+
+        let value = AvroValue::try_from(json.clone())
+            .context("Payload cannot be converted to an Avro value")?
+            .resolve(schema)
+            .with_context(|| format!("Payload does not match Avro schema with id {}", id))?;
+        let datum = GenericDatumWriter::builder(schema)
+            .build()
+            .and_then(|writer| writer.write_value_to_vec(value))
+            .with_context(|| format!("Failed to encode payload with Avro schema id {}", id))?;
+
+        let mut payload = Vec::with_capacity(WIRE_HEADER_LEN + datum.len());
+        payload.push(WIRE_MAGIC_BYTE);
+        payload.extend_from_slice(&id.to_be_bytes());
+        payload.extend_from_slice(&datum);
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use serde_json::json;
+
+    use super::*;
+
+    const USER_SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "User",
+        "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "email", "type": ["null", "string"], "default": null},
+            {"name": "status", "type": {"type": "enum", "name": "Status", "symbols": ["ACTIVE", "BLOCKED"]}}
+        ]
+    }"#;
+
+    /// Minimal Schema Registry serving `USER_SCHEMA` as id 42 and 404 for
+    /// anything else. Returns the base URL.
+    fn mock_registry() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+
+                let (status, body) = if request_line.starts_with("GET /schemas/ids/42 ") {
+                    ("200 OK", json!({ "schema": USER_SCHEMA }).to_string())
+                } else {
+                    ("404 Not Found", json!({ "error_code": 40403 }).to_string())
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        url
+    }
+
+    fn registry_codec() -> AvroCodec {
+        AvroCodec::new(SchemaSource::Registry {
+            client: reqwest::Client::new(),
+            url: mock_registry(),
+            username: None,
+            password: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn round_trips_through_the_registry() {
+        let mut codec = registry_codec();
+        let user = json!({ "id": 7, "email": "a@example.com", "status": "BLOCKED" });
+
+        let payload = codec.encode(42, &user).await.unwrap();
+        assert_eq!(&payload[..WIRE_HEADER_LEN], &[0, 0, 0, 0, 42]);
+        assert_eq!(wire_schema_id(&payload), Some(42));
+
+        let (id, decoded) = codec.decode(&payload).await.unwrap();
+        assert_eq!(id, 42);
+        assert_eq!(decoded, user);
+    }
+
+    #[tokio::test]
+    async fn rejects_payloads_that_do_not_match_the_schema() {
+        let mut codec = registry_codec();
+        let user = json!({ "id": "seven", "status": "ACTIVE" });
+
+        assert!(codec.encode(42, &user).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reports_unknown_schema_ids() {
+        let mut codec = registry_codec();
+
+        let error = codec.decode(&[0, 0, 0, 0, 9, 2]).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("404"));
+    }
+
+    #[test]
+    fn recognises_the_wire_format() {
+        assert_eq!(wire_schema_id(&[0, 0, 0, 1, 0, 2]), Some(256));
+        assert_eq!(wire_schema_id(b"{\"id\":1}"), None);
+        assert_eq!(wire_schema_id(&[0, 0, 1]), None);
+    }
+}
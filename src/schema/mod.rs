@@ -0,0 +1,17 @@
+pub mod avro;
+pub mod protobuf;
+pub mod registry;
+
+pub use avro::*;
+pub use protobuf::*;
+pub use registry::*;
+
+/// How a record's payload was decoded to JSON, so that an edited payload can
+/// be encoded the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadEncoding {
+    /// Avro in the Confluent wire format, with its schema id.
+    Avro { schema_id: u32 },
+    /// The protobuf message type configured for the topic.
+    Protobuf,
+}
@@ -0,0 +1,57 @@
+use anyhow::{anyhow, Context};
+use prost_reflect::{prost::Message, DescriptorPool, DynamicMessage, MessageDescriptor};
+use serde_json::Value;
+
+use crate::settings::Settings;
+
+/// Decodes and encodes the protobuf message type configured for a topic.
+pub struct ProtobufCodec {
+    descriptor: MessageDescriptor,
+}
+
+impl ProtobufCodec {
+    /// A codec for `topic`, if a message type is configured for it.
+    pub fn from_settings(settings: &Settings, topic: &str) -> Result<Option<Self>, anyhow::Error> {
+        let Some(payload) = settings.protobuf_payload(topic) else {
+            return Ok(None);
+        };
+        let Some(message_type) = payload.message_type else {
+            return Ok(None);
+        };
+        let path = payload.descriptor_set.ok_or_else(|| {
+            anyhow!("No descriptor_set configured for protobuf message {}", message_type)
+        })?;
+
+        let bytes = std::fs::read(&path)
+            .with_context(|| format!("Failed to read descriptor set: {}", path.display()))?;
+        let pool = DescriptorPool::decode(bytes.as_slice())
+            .with_context(|| format!("Invalid FileDescriptorSet: {}", path.display()))?;
+        let descriptor = pool.get_message_by_name(&message_type).ok_or_else(|| {
+            anyhow!("Message type {} not found in {}", message_type, path.display())
+        })?;
+
+        Ok(Some(Self { descriptor }))
+    }
+
+    /// Fully qualified name of the message type.
+    pub fn message_type(&self) -> &str {
+        self.descriptor.full_name()
+    }
+
+    /// The message as JSON, using the protobuf JSON mapping.
+    pub fn decode(&self, payload: &[u8]) -> Result<Value, anyhow::Error> {
+        let message = DynamicMessage::decode(self.descriptor.clone(), payload)
+            .with_context(|| format!("Failed to decode payload as {}", self.message_type()))?;
+        serde_json::to_value(&message)
+            .with_context(|| format!("Failed to convert {} to JSON", self.message_type()))
+    }
+
+    /// `json` validated against the message type and encoded. Unknown fields
+    /// are rejected.
+    pub fn encode(&self, json: &Value) -> Result<Vec<u8>, anyhow::Error> {
+        let message = DynamicMessage::deserialize(self.descriptor.clone(), json)
+            .with_context(|| format!("Payload does not match {}", self.message_type()))?;
+        Ok(message.encode_to_vec())
+    }
+}
+
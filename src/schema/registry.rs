@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+
+use crate::settings::{Secret, Settings};
+
+/// Where schemas are looked up by their registry id.
+#[derive(Debug, Clone)]
+pub enum SchemaSource {
+    /// A Confluent-compatible Schema Registry.
+    Registry {
+        client: reqwest::Client,
+        url: String,
+        username: Option<String>,
+        password: Option<Secret>,
+    },
+    /// A directory holding one `<schema id>.avsc` file per schema.
+    Directory(PathBuf),
+}
+
+/// Body of `GET /schemas/ids/{id}`.
+#[derive(Deserialize)]
+struct RegistrySchema {
+    schema: String,
+}
+
+impl SchemaSource {
+    /// The configured source, preferring the registry over a directory.
+    pub fn from_settings(settings: &Settings) -> Option<Self> {
+        if let Some(url) = &settings.schema_registry_url {
+            return Some(SchemaSource::Registry {
+                client: reqwest::Client::new(),
+                url: url.trim_end_matches('/').to_string(),
+                username: settings.security.schema_registry_username.clone(),
+                password: settings.security.schema_registry_password.clone(),
+            });
+        }
+        settings.avsc_dir.clone().map(SchemaSource::Directory)
+    }
+
+    /// Schema text for `id`.
+    pub async fn fetch(&self, id: u32) -> Result<String, anyhow::Error> {
+        match self {
+            SchemaSource::Registry { client, url, username, password } => {
+                let mut request = client.get(format!("{}/schemas/ids/{}", url, id));
+                if let Some(username) = username {
+                    request = request.basic_auth(username, password.as_ref().map(|p| p.expose()));
+                }
+
+                let response = request
+                    .send()
+                    .await
+                    .with_context(|| format!("Failed to reach Schema Registry at {}", url))?;
+                if !response.status().is_success() {
+                    return Err(anyhow!(
+                        "Schema Registry returned {} for schema id {}",
+                        response.status(),
+                        id
+                    ));
+                }
+                let body: RegistrySchema = response
+                    .json()
+                    .await
+                    .with_context(|| format!("Invalid Schema Registry response for schema id {}", id))?;
+                Ok(body.schema)
+            }
+            SchemaSource::Directory(dir) => {
+                let path = dir.join(format!("{}.avsc", id));
+                std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read schema file: {}", path.display()))
+            }
+        }
+    }
+}
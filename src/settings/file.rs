@@ -0,0 +1,210 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::settings::{EnvelopeFields, RawFields, SecurityProfile, TopicProfile};
+
+// Config file locations
+pub const PROJECT_CONFIG_FILE: &str = "poison-queue.toml";
+pub const USER_CONFIG_DIR: &str = "poison-queue";
+pub const USER_CONFIG_FILE: &str = "config.toml";
+
+/// Contents of a `poison-queue.toml` file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Profile used when none is given on the command line.
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// A named cluster profile. Every field is optional so that profiles from
+/// several files can be layered on top of each other.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub brokers: Option<String>,
+    pub group_id: Option<String>,
+    pub archive_topic: Option<String>,
+    pub dlq: Option<DlqNaming>,
+    pub security: Option<SecurityProfile>,
+    /// Extra librdkafka properties for consumers
+    pub consumer: Option<BTreeMap<String, PropertyValue>>,
+    /// Extra librdkafka properties for producers
+    pub producer: Option<BTreeMap<String, PropertyValue>>,
+    /// Envelope field mapping for every topic of the profile
+    pub envelope: Option<EnvelopeFields>,
+    /// Headers read in raw mode for every topic of the profile
+    pub raw: Option<RawFields>,
+    /// Where Avro schemas for Confluent wire format payloads come from
+    pub schemas: Option<SchemaProfile>,
+    /// Overrides for topics matching a pattern
+    pub topics: Option<BTreeMap<String, TopicProfile>>,
+}
+
+/// A librdkafka property value. TOML numbers and booleans are accepted so
+/// that `"fetch.max.bytes" = 1048576` works without quoting.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum PropertyValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl fmt::Display for PropertyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropertyValue::String(value) => f.write_str(value),
+            PropertyValue::Integer(value) => write!(f, "{}", value),
+            PropertyValue::Float(value) => write!(f, "{}", value),
+            PropertyValue::Boolean(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Naming rules used to recognise DLQ topics.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DlqNaming {
+    pub prefixes: Option<Vec<String>>,
+    pub suffixes: Option<Vec<String>>,
+}
+
+/// `[profiles.<name>.schemas]` section: a Schema Registry, or a directory
+/// holding one `<schema id>.avsc` file per schema, for Avro payloads, and
+/// the default `FileDescriptorSet` for protobuf payloads.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SchemaProfile {
+    pub registry_url: Option<String>,
+    pub avsc_dir: Option<PathBuf>,
+    pub descriptor_set: Option<PathBuf>,
+}
+
+impl ConfigFile {
+    /// Read and parse a config file.
+    pub fn read(path: &Path) -> Result<Self, anyhow::Error> {
+        let content = std::fs::read_to_string(path)
+            .context(format!("Failed to read config file: {}", path.display()))?;
+
+        toml::from_str(&content)
+            .context(format!("Invalid config file: {}", path.display()))
+    }
+
+    /// Layer `other` on top of `self`; values set in `other` win.
+    pub fn merge(mut self, other: ConfigFile) -> Self {
+        if other.default_profile.is_some() {
+            self.default_profile = other.default_profile;
+        }
+        for (name, profile) in other.profiles {
+            let merged = match self.profiles.remove(&name) {
+                Some(base) => base.merge(profile),
+                None => profile,
+            };
+            self.profiles.insert(name, merged);
+        }
+        self
+    }
+}
+
+impl Profile {
+    /// Layer `other` on top of `self`; values set in `other` win.
+    pub fn merge(self, other: Profile) -> Self {
+        Self {
+            brokers: other.brokers.or(self.brokers),
+            group_id: other.group_id.or(self.group_id),
+            archive_topic: other.archive_topic.or(self.archive_topic),
+            dlq: match (self.dlq, other.dlq) {
+                (Some(base), Some(over)) => Some(DlqNaming {
+                    prefixes: over.prefixes.or(base.prefixes),
+                    suffixes: over.suffixes.or(base.suffixes),
+                }),
+                (base, over) => over.or(base),
+            },
+            security: match (self.security, other.security) {
+                (Some(base), Some(over)) => Some(base.merge(over)),
+                (base, over) => over.or(base),
+            },
+            consumer: merge_properties(self.consumer, other.consumer),
+            producer: merge_properties(self.producer, other.producer),
+            envelope: match (self.envelope, other.envelope) {
+                (Some(base), Some(over)) => Some(base.merge(over)),
+                (base, over) => over.or(base),
+            },
+            raw: match (self.raw, other.raw) {
+                (Some(base), Some(over)) => Some(base.merge(over)),
+                (base, over) => over.or(base),
+            },
+            schemas: match (self.schemas, other.schemas) {
+                (Some(base), Some(over)) => Some(SchemaProfile {
+                    registry_url: over.registry_url.or(base.registry_url),
+                    avsc_dir: over.avsc_dir.or(base.avsc_dir),
+                    descriptor_set: over.descriptor_set.or(base.descriptor_set),
+                }),
+                (base, over) => over.or(base),
+            },
+            topics: match (self.topics, other.topics) {
+                (Some(mut base), Some(over)) => {
+                    for (pattern, topic) in over {
+                        let merged = match base.remove(&pattern) {
+                            Some(existing) => existing.merge(topic),
+                            None => topic,
+                        };
+                        base.insert(pattern, merged);
+                    }
+                    Some(base)
+                }
+                (base, over) => over.or(base),
+            },
+        }
+    }
+}
+
+fn merge_properties(
+    base: Option<BTreeMap<String, PropertyValue>>,
+    over: Option<BTreeMap<String, PropertyValue>>,
+) -> Option<BTreeMap<String, PropertyValue>> {
+    match (base, over) {
+        (Some(mut base), Some(over)) => {
+            base.extend(over);
+            Some(base)
+        }
+        (base, over) => over.or(base),
+    }
+}
+
+/// Config files to load, lowest precedence first: the user config
+/// (`~/.config/poison-queue/config.toml`) and then the project-local
+/// `poison-queue.toml` in the current directory.
+pub fn default_config_paths() -> Vec<PathBuf> {
+    let mut paths = vec![];
+    if let Some(dir) = user_config_dir() {
+        paths.push(dir.join(USER_CONFIG_FILE));
+    }
+    paths.push(PathBuf::from(PROJECT_CONFIG_FILE));
+    paths
+}
+
+/// `$XDG_CONFIG_HOME/poison-queue`, falling back to `~/.config/poison-queue`.
+pub fn user_config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join(USER_CONFIG_DIR))
+}
+
+/// Expand a leading `~/` to the user's home directory.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
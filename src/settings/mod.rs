@@ -0,0 +1,269 @@
+pub mod file;
+pub mod security;
+pub mod topic;
+
+pub use file::*;
+pub use security::*;
+pub use topic::*;
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::anyhow;
+
+use crate::{
+    cli::{EnvelopeMapping, GlobalArgs, PropertyScope},
+    envelope::EnvelopeFormat,
+    kafka::validate_properties,
+};
+
+// Built-in defaults, used when neither a profile nor an override sets a value
+pub const DEFAULT_BROKERS: &str = "localhost:9092";
+pub const DEFAULT_GROUP_ID: &str = "poison_queue_cli_consumer_group_id";
+pub const DEFAULT_ARCHIVE_TOPIC: &str = "dlq-archive";
+pub const DEFAULT_DLQ_PREFIX: &str = "dlq-";
+pub const DEFAULT_DLQ_SUFFIX: &str = "-dlq";
+
+/// Fully resolved settings shared by every command.
+///
+/// Values are layered as: built-in defaults, then the selected profile from
+/// the config files, then `POISON_QUEUE_*` environment variables, then
+/// command-line flags.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub profile: Option<String>,
+    pub brokers: String,
+    pub group_id: String,
+    pub archive_topic: String,
+    pub dlq_prefixes: Vec<String>,
+    pub dlq_suffixes: Vec<String>,
+    pub security: Security,
+    /// Extra librdkafka properties applied to every consumer
+    pub consumer_properties: BTreeMap<String, String>,
+    /// Extra librdkafka properties applied to every producer
+    pub producer_properties: BTreeMap<String, String>,
+    /// Envelope field mapping shared by every topic
+    pub envelope: EnvelopeFields,
+    /// Raw mode headers shared by every topic
+    pub raw: RawFields,
+    /// Schema Registry used to decode Avro payloads
+    pub schema_registry_url: Option<String>,
+    /// Directory of `<schema id>.avsc` files, used when there is no registry
+    pub avsc_dir: Option<PathBuf>,
+    /// Default `FileDescriptorSet` for protobuf payloads
+    pub descriptor_set: Option<PathBuf>,
+    /// Per-topic overrides keyed by topic pattern
+    pub topics: BTreeMap<String, TopicProfile>,
+}
+
+impl Settings {
+    pub fn load(args: &GlobalArgs) -> Result<Self, anyhow::Error> {
+        let config = match &args.config {
+            Some(path) => ConfigFile::read(path)?,
+            None => {
+                let mut config = ConfigFile::default();
+                for path in default_config_paths() {
+                    if path.is_file() {
+                        config = config.merge(ConfigFile::read(&path)?);
+                    }
+                }
+                config
+            }
+        };
+
+        Self::resolve(config, args)
+    }
+
+    fn resolve(mut config: ConfigFile, args: &GlobalArgs) -> Result<Self, anyhow::Error> {
+        let profile_name = args.profile.clone().or(config.default_profile.clone());
+        let profile = match &profile_name {
+            Some(name) => config
+                .profiles
+                .remove(name)
+                .ok_or_else(|| anyhow!("Unknown profile: {}", name))?,
+            None => Profile::default(),
+        };
+        let dlq = profile.dlq.unwrap_or_default();
+        let schemas = profile.schemas.unwrap_or_default();
+        let security = Security::resolve(
+            profile.security.unwrap_or_default(),
+            profile_name.as_deref(),
+        )?;
+
+        let mut consumer_properties = to_properties(profile.consumer);
+        let mut producer_properties = to_properties(profile.producer);
+        // Unscoped flags first so that `consumer:`/`producer:` flags win
+        for scoped in [false, true] {
+            for property in &args.properties {
+                if (property.scope != PropertyScope::All) != scoped {
+                    continue;
+                }
+                if property.scope != PropertyScope::Producer {
+                    consumer_properties.insert(property.key.clone(), property.value.clone());
+                }
+                if property.scope != PropertyScope::Consumer {
+                    producer_properties.insert(property.key.clone(), property.value.clone());
+                }
+            }
+        }
+        validate_properties("consumer", &consumer_properties)?;
+        validate_properties("producer", &producer_properties)?;
+
+        let envelope = profile.envelope.unwrap_or_default();
+        envelope.validate()?;
+        let topics = profile.topics.unwrap_or_default();
+        for (pattern, topic) in &topics {
+            if let Some(envelope) = &topic.envelope {
+                envelope
+                    .validate()
+                    .map_err(|e| anyhow!("{} (topic `{}`)", e, pattern))?;
+            }
+        }
+
+        Ok(Self {
+            profile: profile_name,
+            brokers: args
+                .brokers
+                .clone()
+                .or(profile.brokers)
+                .unwrap_or_else(|| DEFAULT_BROKERS.to_string()),
+            group_id: args
+                .group_id
+                .clone()
+                .or(profile.group_id)
+                .unwrap_or_else(|| DEFAULT_GROUP_ID.to_string()),
+            archive_topic: args
+                .archive_topic
+                .clone()
+                .or(profile.archive_topic)
+                .unwrap_or_else(|| DEFAULT_ARCHIVE_TOPIC.to_string()),
+            dlq_prefixes: dlq
+                .prefixes
+                .unwrap_or_else(|| vec![DEFAULT_DLQ_PREFIX.to_string()]),
+            dlq_suffixes: dlq
+                .suffixes
+                .unwrap_or_else(|| vec![DEFAULT_DLQ_SUFFIX.to_string()]),
+            security,
+            consumer_properties,
+            producer_properties,
+            envelope,
+            raw: profile.raw.unwrap_or_default(),
+            schema_registry_url: args.schema_registry_url.clone().or(schemas.registry_url),
+            avsc_dir: schemas.avsc_dir.map(|dir| expand_home(&dir)),
+            descriptor_set: schemas.descriptor_set.map(|path| expand_home(&path)),
+            topics,
+        })
+    }
+
+    /// Whether a topic is a dead letter queue according to the naming rules.
+    pub fn is_dlq_topic(&self, topic: &str) -> bool {
+        self.dlq_prefixes.iter().any(|prefix| topic.starts_with(prefix.as_str()))
+            || self.dlq_suffixes.iter().any(|suffix| topic.ends_with(suffix.as_str()))
+    }
+
+    /// Overrides for `topic`: an exact entry if there is one, otherwise the
+    /// longest matching pattern.
+    pub fn topic_profile(&self, topic: &str) -> Option<&TopicProfile> {
+        self.topics.get(topic).or_else(|| {
+            self.topics
+                .iter()
+                .filter(|(pattern, _)| topic_matches(pattern, topic))
+                .max_by_key(|(pattern, _)| pattern.len())
+                .map(|(_, profile)| profile)
+        })
+    }
+
+    /// Format pinned for `topic` in the config, if any; otherwise the
+    /// scanner detects it.
+    pub fn envelope_format(&self, topic: &str) -> Option<EnvelopeFormat> {
+        self.topic_profile(topic).and_then(|t| t.format)
+    }
+
+    /// Protobuf type configured for the payloads of `topic`, with the
+    /// descriptor set falling back to the profile's.
+    pub fn protobuf_payload(&self, topic: &str) -> Option<ProtobufPayload> {
+        let protobuf = self.topic_profile(topic)?.protobuf.clone()?;
+        Some(ProtobufPayload {
+            descriptor_set: protobuf
+                .descriptor_set
+                .map(|path| expand_home(&path))
+                .or_else(|| self.descriptor_set.clone()),
+            message_type: protobuf.message_type,
+        })
+    }
+
+    /// Envelope mapping for `topic`: built-in defaults, then the profile's
+    /// `envelope` and `raw` sections, then the topic's.
+    pub fn envelope_mapping(&self, topic: &str) -> EnvelopeMapping {
+        let topic = self.topic_profile(topic);
+        let mut fields = self.envelope.clone();
+        if let Some(envelope) = topic.and_then(|t| t.envelope.clone()) {
+            fields = fields.merge(envelope);
+        }
+        let mut raw = self.raw.clone();
+        if let Some(topic_raw) = topic.and_then(|t| t.raw.clone()) {
+            raw = raw.merge(topic_raw);
+        }
+        EnvelopeMapping::from_fields(fields, raw)
+    }
+}
+
+fn to_properties(values: Option<BTreeMap<String, PropertyValue>>) -> BTreeMap<String, String> {
+    values
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(key, value)| (key, value.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_topics(patterns: &[(&str, EnvelopeFormat)]) -> Settings {
+        Settings {
+            profile: None,
+            brokers: String::new(),
+            group_id: String::new(),
+            archive_topic: String::new(),
+            dlq_prefixes: vec![],
+            dlq_suffixes: vec![],
+            security: Security::default(),
+            consumer_properties: BTreeMap::new(),
+            producer_properties: BTreeMap::new(),
+            envelope: EnvelopeFields::default(),
+            raw: RawFields::default(),
+            schema_registry_url: None,
+            avsc_dir: None,
+            descriptor_set: None,
+            topics: patterns
+                .iter()
+                .map(|(pattern, format)| {
+                    (pattern.to_string(), TopicProfile { format: Some(*format), ..Default::default() })
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn an_exact_entry_wins_over_patterns() {
+        let settings = with_topics(&[
+            ("dlq-orders-*", EnvelopeFormat::SpringDlt),
+            ("dlq-orders-v2", EnvelopeFormat::Json),
+            ("*", EnvelopeFormat::Raw),
+        ]);
+        assert_eq!(settings.envelope_format("dlq-orders-v2"), Some(EnvelopeFormat::Json));
+    }
+
+    #[test]
+    fn the_longest_matching_pattern_wins() {
+        let settings = with_topics(&[
+            ("*", EnvelopeFormat::Raw),
+            ("dlq-*", EnvelopeFormat::Connect),
+            ("dlq-orders-*", EnvelopeFormat::SpringDlt),
+        ]);
+        assert_eq!(settings.envelope_format("dlq-orders-v2"), Some(EnvelopeFormat::SpringDlt));
+        assert_eq!(settings.envelope_format("dlq-payments"), Some(EnvelopeFormat::Connect));
+        assert_eq!(settings.envelope_format("orders"), Some(EnvelopeFormat::Raw));
+        assert_eq!(with_topics(&[]).envelope_format("orders"), None);
+    }
+}
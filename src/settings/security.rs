@@ -0,0 +1,199 @@
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+
+use crate::settings::{expand_home, user_config_dir};
+
+// Credential environment variables
+pub const ENV_SASL_USERNAME: &str = "POISON_QUEUE_SASL_USERNAME";
+pub const ENV_SASL_PASSWORD: &str = "POISON_QUEUE_SASL_PASSWORD";
+pub const ENV_SSL_KEY_PASSWORD: &str = "POISON_QUEUE_SSL_KEY_PASSWORD";
+pub const ENV_CREDENTIALS_FILE: &str = "POISON_QUEUE_CREDENTIALS_FILE";
+pub const ENV_SCHEMA_REGISTRY_USERNAME: &str = "POISON_QUEUE_SCHEMA_REGISTRY_USERNAME";
+pub const ENV_SCHEMA_REGISTRY_PASSWORD: &str = "POISON_QUEUE_SCHEMA_REGISTRY_PASSWORD";
+
+pub const CREDENTIALS_FILE: &str = "credentials.toml";
+// Credentials section used when no profile is selected
+pub const DEFAULT_CREDENTIALS_SECTION: &str = "default";
+
+pub const SECURITY_PROTOCOLS: &[&str] = &["PLAINTEXT", "SSL", "SASL_PLAINTEXT", "SASL_SSL"];
+pub const SASL_MECHANISMS: &[&str] = &["PLAIN", "SCRAM-SHA-256", "SCRAM-SHA-512"];
+
+/// `[profiles.<name>.security]` section of a config file. Secrets are not
+/// accepted here; they come from the environment or the credentials file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecurityProfile {
+    pub protocol: Option<String>,
+    pub sasl_mechanism: Option<String>,
+    pub ssl_ca_location: Option<PathBuf>,
+    pub ssl_certificate_location: Option<PathBuf>,
+    pub ssl_key_location: Option<PathBuf>,
+    pub credentials_file: Option<PathBuf>,
+}
+
+impl SecurityProfile {
+    /// Layer `other` on top of `self`; values set in `other` win.
+    pub fn merge(self, other: SecurityProfile) -> Self {
+        Self {
+            protocol: other.protocol.or(self.protocol),
+            sasl_mechanism: other.sasl_mechanism.or(self.sasl_mechanism),
+            ssl_ca_location: other.ssl_ca_location.or(self.ssl_ca_location),
+            ssl_certificate_location: other.ssl_certificate_location.or(self.ssl_certificate_location),
+            ssl_key_location: other.ssl_key_location.or(self.ssl_key_location),
+            credentials_file: other.credentials_file.or(self.credentials_file),
+        }
+    }
+}
+
+/// One section of the credentials file, keyed by profile name.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Credentials {
+    sasl_username: Option<String>,
+    sasl_password: Option<Secret>,
+    ssl_key_password: Option<Secret>,
+    schema_registry_username: Option<String>,
+    schema_registry_password: Option<Secret>,
+}
+
+/// A credential that never shows up in `Debug` output.
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"****\"")
+    }
+}
+
+/// Resolved security settings applied to every Kafka client.
+#[derive(Debug, Clone, Default)]
+pub struct Security {
+    pub protocol: Option<String>,
+    pub sasl_mechanism: Option<String>,
+    pub sasl_username: Option<String>,
+    pub sasl_password: Option<Secret>,
+    pub ssl_ca_location: Option<PathBuf>,
+    pub ssl_certificate_location: Option<PathBuf>,
+    pub ssl_key_location: Option<PathBuf>,
+    pub ssl_key_password: Option<Secret>,
+    /// Basic auth for the Schema Registry
+    pub schema_registry_username: Option<String>,
+    pub schema_registry_password: Option<Secret>,
+}
+
+impl Security {
+    pub fn resolve(
+        profile: SecurityProfile,
+        profile_name: Option<&str>,
+    ) -> Result<Self, anyhow::Error> {
+        let protocol = profile.protocol.map(|p| p.to_uppercase());
+        if let Some(protocol) = &protocol {
+            if !SECURITY_PROTOCOLS.contains(&protocol.as_str()) {
+                return Err(anyhow!(
+                    "Unsupported security protocol: {} (expected one of {})",
+                    protocol,
+                    SECURITY_PROTOCOLS.join(", ")
+                ));
+            }
+        }
+
+        let sasl_mechanism = profile.sasl_mechanism.map(|m| m.to_uppercase());
+        if let Some(mechanism) = &sasl_mechanism {
+            if !SASL_MECHANISMS.contains(&mechanism.as_str()) {
+                return Err(anyhow!(
+                    "Unsupported SASL mechanism: {} (expected one of {})",
+                    mechanism,
+                    SASL_MECHANISMS.join(", ")
+                ));
+            }
+        }
+
+        let credentials_file = std::env::var_os(ENV_CREDENTIALS_FILE)
+            .map(PathBuf::from)
+            .or(profile.credentials_file)
+            .map(|path| expand_home(&path));
+        let credentials = read_credentials(credentials_file, profile_name)?;
+
+        let security = Self {
+            sasl_username: env_var(ENV_SASL_USERNAME).or(credentials.sasl_username),
+            sasl_password: env_var(ENV_SASL_PASSWORD).map(Secret).or(credentials.sasl_password),
+            ssl_key_password: env_var(ENV_SSL_KEY_PASSWORD).map(Secret).or(credentials.ssl_key_password),
+            schema_registry_username: env_var(ENV_SCHEMA_REGISTRY_USERNAME)
+                .or(credentials.schema_registry_username),
+            schema_registry_password: env_var(ENV_SCHEMA_REGISTRY_PASSWORD)
+                .map(Secret)
+                .or(credentials.schema_registry_password),
+            ssl_ca_location: profile.ssl_ca_location.map(|p| expand_home(&p)),
+            ssl_certificate_location: profile.ssl_certificate_location.map(|p| expand_home(&p)),
+            ssl_key_location: profile.ssl_key_location.map(|p| expand_home(&p)),
+            protocol,
+            sasl_mechanism,
+        };
+
+        if security.uses_sasl() {
+            if security.sasl_mechanism.is_none() {
+                return Err(anyhow!(
+                    "Security protocol {} requires sasl_mechanism",
+                    security.protocol.as_deref().unwrap_or_default()
+                ));
+            }
+            if security.sasl_username.is_none() || security.sasl_password.is_none() {
+                return Err(anyhow!(
+                    "SASL credentials missing: set {} and {} or add them to the credentials file",
+                    ENV_SASL_USERNAME,
+                    ENV_SASL_PASSWORD
+                ));
+            }
+        }
+
+        Ok(security)
+    }
+
+    pub fn uses_sasl(&self) -> bool {
+        self.protocol.as_deref().is_some_and(|p| p.starts_with("SASL_"))
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.is_empty())
+}
+
+/// Read the credentials for a profile. An explicitly configured file must
+/// exist; the default `~/.config/poison-queue/credentials.toml` is optional.
+fn read_credentials(
+    path: Option<PathBuf>,
+    profile_name: Option<&str>,
+) -> Result<Credentials, anyhow::Error> {
+    let path = match path {
+        Some(path) => path,
+        None => match user_config_dir() {
+            Some(dir) if dir.join(CREDENTIALS_FILE).is_file() => dir.join(CREDENTIALS_FILE),
+            _ => return Ok(Credentials::default()),
+        },
+    };
+
+    let content = std::fs::read_to_string(&path)
+        .context(format!("Failed to read credentials file: {}", path.display()))?;
+    // The parse error quotes the offending line, so only report its position
+    let mut sections: BTreeMap<String, Credentials> = toml::from_str(&content).map_err(|e| {
+        let line = e
+            .span()
+            .map(|span| content[..span.start].matches('\n').count() + 1)
+            .unwrap_or(1);
+        anyhow!("Invalid credentials file: {} (line {})", path.display(), line)
+    })?;
+
+    Ok(sections
+        .remove(profile_name.unwrap_or(DEFAULT_CREDENTIALS_SECTION))
+        .unwrap_or_default())
+}
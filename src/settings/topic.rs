@@ -0,0 +1,193 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use crate::envelope::EnvelopeFormat;
+
+/// Per-topic overrides, configured as `[profiles.<name>.topics."<pattern>"]`.
+/// The pattern is a topic name in which `*` matches any run of characters.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TopicProfile {
+    /// DLQ convention to decode with instead of detecting it.
+    pub format: Option<EnvelopeFormat>,
+    pub envelope: Option<EnvelopeFields>,
+    pub raw: Option<RawFields>,
+    pub protobuf: Option<ProtobufPayload>,
+}
+
+impl TopicProfile {
+    /// Layer `other` on top of `self`; values set in `other` win.
+    pub fn merge(self, other: TopicProfile) -> Self {
+        Self {
+            format: other.format.or(self.format),
+            envelope: match (self.envelope, other.envelope) {
+                (Some(base), Some(over)) => Some(base.merge(over)),
+                (base, over) => over.or(base),
+            },
+            raw: match (self.raw, other.raw) {
+                (Some(base), Some(over)) => Some(base.merge(over)),
+                (base, over) => over.or(base),
+            },
+            protobuf: match (self.protobuf, other.protobuf) {
+                (Some(base), Some(over)) => Some(ProtobufPayload {
+                    descriptor_set: over.descriptor_set.or(base.descriptor_set),
+                    message_type: over.message_type.or(base.message_type),
+                }),
+                (base, over) => over.or(base),
+            },
+        }
+    }
+}
+
+/// Protobuf type of the payloads in a topic. `descriptor_set` defaults to
+/// the profile's `schemas.descriptor_set`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProtobufPayload {
+    pub descriptor_set: Option<PathBuf>,
+    /// Fully qualified message name, e.g. `acme.orders.v1.Order`
+    pub message_type: Option<String>,
+}
+
+/// Headers holding the failure context of records decoded in raw mode.
+/// Without them the id is the record key and the reason is unknown.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawFields {
+    pub id_header: Option<String>,
+    pub reason_header: Option<String>,
+    pub original_topic_header: Option<String>,
+}
+
+impl RawFields {
+    /// Layer `other` on top of `self`; values set in `other` win.
+    pub fn merge(self, other: RawFields) -> Self {
+        Self {
+            id_header: other.id_header.or(self.id_header),
+            reason_header: other.reason_header.or(self.reason_header),
+            original_topic_header: other.original_topic_header.or(self.original_topic_header),
+        }
+    }
+}
+
+/// Where the envelope fields live in a DLQ message, as JSON Pointers
+/// (RFC 6901), e.g. `reason = "/error/message"`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvelopeFields {
+    pub id: Option<String>,
+    pub correlation_id: Option<String>,
+    pub payload: Option<String>,
+    pub metadata: Option<String>,
+    pub reason: Option<String>,
+    pub retries: Option<String>,
+    pub original_topic: Option<String>,
+    pub moved_at: Option<String>,
+    pub archived_at: Option<String>,
+    pub original_timestamp: Option<String>,
+}
+
+impl EnvelopeFields {
+    /// Layer `other` on top of `self`; values set in `other` win.
+    pub fn merge(self, other: EnvelopeFields) -> Self {
+        Self {
+            id: other.id.or(self.id),
+            correlation_id: other.correlation_id.or(self.correlation_id),
+            payload: other.payload.or(self.payload),
+            metadata: other.metadata.or(self.metadata),
+            reason: other.reason.or(self.reason),
+            retries: other.retries.or(self.retries),
+            original_topic: other.original_topic.or(self.original_topic),
+            moved_at: other.moved_at.or(self.moved_at),
+            archived_at: other.archived_at.or(self.archived_at),
+            original_timestamp: other.original_timestamp.or(self.original_timestamp),
+        }
+    }
+
+    /// Check that every configured value is a JSON Pointer.
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let fields = [
+            ("id", &self.id),
+            ("correlation_id", &self.correlation_id),
+            ("payload", &self.payload),
+            ("metadata", &self.metadata),
+            ("reason", &self.reason),
+            ("retries", &self.retries),
+            ("original_topic", &self.original_topic),
+            ("moved_at", &self.moved_at),
+            ("archived_at", &self.archived_at),
+            ("original_timestamp", &self.original_timestamp),
+        ];
+        for (name, pointer) in fields {
+            if let Some(pointer) = pointer {
+                if !pointer.is_empty() && !pointer.starts_with('/') {
+                    return Err(anyhow!(
+                        "Invalid envelope.{}: `{}` is not a JSON Pointer (expected e.g. `/{}`)",
+                        name,
+                        pointer,
+                        pointer
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `topic` matches a pattern in which `*` matches any run of characters.
+pub fn topic_matches(pattern: &str, topic: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = topic.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*` in the pattern
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_exact_names() {
+        assert!(topic_matches("dlq-orders", "dlq-orders"));
+        assert!(!topic_matches("dlq-orders", "dlq-orders-v2"));
+        assert!(!topic_matches("dlq-orders", "dlq-order"));
+    }
+
+    #[test]
+    fn star_matches_any_run_of_characters() {
+        // At the end
+        assert!(topic_matches("dlq-*", "dlq-orders"));
+        assert!(topic_matches("dlq-*", "dlq-"));
+        assert!(!topic_matches("dlq-*", "orders-dlq"));
+        // At the start
+        assert!(topic_matches("*.dlq", "orders.dlq"));
+        assert!(!topic_matches("*.dlq", "orders.dlq.v2"));
+        // In the middle
+        assert!(topic_matches("dlq-*-v2", "dlq-orders-v2"));
+        assert!(topic_matches("dlq-*-v2", "dlq--v2"));
+        assert!(!topic_matches("dlq-*-v2", "dlq-v2"));
+        // Several
+        assert!(topic_matches("*", "anything"));
+        assert!(topic_matches("*-*-dlq", "a-b-c-dlq"));
+        assert!(!topic_matches("*-*-dlq", "a-dlq"));
+        // The prefix and suffix may not overlap
+        assert!(!topic_matches("ab*ba", "aba"));
+    }
+}